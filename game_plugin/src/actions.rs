@@ -1,17 +1,80 @@
+use crate::physics::GameStage;
+use crate::settings::Settings;
 use crate::GameState;
 use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_rapier2d::physics::PhysicsSystems;
+use serde::{Deserialize, Serialize};
 
 pub struct ActionsPlugin;
 
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_resource::<Actions>().add_system_set(
-            SystemSet::on_update(GameState::InLevel).with_system(set_movement_actions.system()),
-        );
+        app.init_resource::<Actions>()
+            .insert_resource(InputSource::Player)
+            .init_resource::<ConnectedGamepads>()
+            .init_resource::<KeyBindings>()
+            .add_system(track_gamepads.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel).with_system(
+                    set_movement_actions
+                        .system()
+                        .label(ActionsSystem::SetMovementActions),
+                ),
+            )
+            .add_system_to_stage(
+                GameStage::PhysicsStep,
+                consume_one_shot_actions
+                    .system()
+                    .after(PhysicsSystems::StepWorld),
+            );
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum ActionsSystem {
+    SetMovementActions,
+    /// Records or plays back the actions of a physics step. Everything in the step reading
+    /// [`Actions`] runs after it.
+    Replay,
+}
+
+/// Where the [`Actions`] in a level come from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputSource {
+    /// Keyboard, gamepad and touch input
+    Player,
+    /// Frames of a replay file
+    Replay,
+    /// The autopilot balancing the rider on its own
+    Autopilot,
+    /// Written from outside of the game's systems, e.g. by tests driving a headless app
+    External,
+}
+
+/// All currently connected gamepads
+#[derive(Default)]
+pub struct ConnectedGamepads(pub HashSet<Gamepad>);
+
+fn track_gamepads(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut gamepads: ResMut<ConnectedGamepads>,
+) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                gamepads.0.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                gamepads.0.remove(gamepad);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Input for the rider. Jumping and restarting only count for the next physics step.
+#[derive(Clone, Default, Debug, Deserialize, Serialize)]
 pub struct Actions {
     pub jump: bool,
     pub paddling: Option<f32>,
@@ -19,26 +82,44 @@ pub struct Actions {
     pub restart: bool,
 }
 
-fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<KeyCode>>) {
-    if GameControl::PaddleBackward.just_released(&keyboard_input)
-        || GameControl::PaddleBackward.pressed(&keyboard_input)
-        || GameControl::PaddleForward.just_released(&keyboard_input)
-        || GameControl::PaddleForward.pressed(&keyboard_input)
+fn consume_one_shot_actions(mut actions: ResMut<Actions>) {
+    actions.jump = false;
+    actions.restart = false;
+}
+
+fn set_movement_actions(
+    mut actions: ResMut<Actions>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    gamepads: Res<ConnectedGamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    settings: Res<Settings>,
+    source: Res<InputSource>,
+) {
+    if *source != InputSource::Player {
+        return;
+    }
+    if GameControl::PaddleBackward.just_released(&bindings, &keyboard_input)
+        || GameControl::PaddleBackward.pressed(&bindings, &keyboard_input)
+        || GameControl::PaddleForward.just_released(&bindings, &keyboard_input)
+        || GameControl::PaddleForward.pressed(&bindings, &keyboard_input)
     {
         let mut paddling = actions.paddling.unwrap_or(0.);
-        if GameControl::PaddleForward.just_released(&keyboard_input)
-            || GameControl::PaddleBackward.just_released(&keyboard_input)
+        if GameControl::PaddleForward.just_released(&bindings, &keyboard_input)
+            || GameControl::PaddleBackward.just_released(&bindings, &keyboard_input)
         {
-            if GameControl::PaddleForward.pressed(&keyboard_input) {
+            if GameControl::PaddleForward.pressed(&bindings, &keyboard_input) {
                 paddling = 1.;
-            } else if GameControl::PaddleBackward.pressed(&keyboard_input) {
+            } else if GameControl::PaddleBackward.pressed(&bindings, &keyboard_input) {
                 paddling = -1.;
             } else {
                 paddling = 0.;
             }
-        } else if GameControl::PaddleForward.just_pressed(&keyboard_input) {
+        } else if GameControl::PaddleForward.just_pressed(&bindings, &keyboard_input) {
             paddling = 1.;
-        } else if GameControl::PaddleBackward.just_pressed(&keyboard_input) {
+        } else if GameControl::PaddleBackward.just_pressed(&bindings, &keyboard_input) {
             paddling = -1.;
         }
         actions.paddling = Some(paddling);
@@ -46,25 +127,25 @@ fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<
         actions.paddling = None;
     }
 
-    if GameControl::BalanceForward.just_released(&keyboard_input)
-        || GameControl::BalanceForward.pressed(&keyboard_input)
-        || GameControl::BalanceBackward.just_released(&keyboard_input)
-        || GameControl::BalanceBackward.pressed(&keyboard_input)
+    if GameControl::BalanceForward.just_released(&bindings, &keyboard_input)
+        || GameControl::BalanceForward.pressed(&bindings, &keyboard_input)
+        || GameControl::BalanceBackward.just_released(&bindings, &keyboard_input)
+        || GameControl::BalanceBackward.pressed(&bindings, &keyboard_input)
     {
         let mut head_balance = actions.head_balance.unwrap_or(0.);
-        if GameControl::BalanceForward.just_released(&keyboard_input)
-            || GameControl::BalanceBackward.just_released(&keyboard_input)
+        if GameControl::BalanceForward.just_released(&bindings, &keyboard_input)
+            || GameControl::BalanceBackward.just_released(&bindings, &keyboard_input)
         {
-            if GameControl::BalanceForward.pressed(&keyboard_input) {
+            if GameControl::BalanceForward.pressed(&bindings, &keyboard_input) {
                 head_balance = 1.;
-            } else if GameControl::BalanceBackward.pressed(&keyboard_input) {
+            } else if GameControl::BalanceBackward.pressed(&bindings, &keyboard_input) {
                 head_balance = -1.;
             } else {
                 head_balance = 0.;
             }
-        } else if GameControl::BalanceForward.just_pressed(&keyboard_input) {
+        } else if GameControl::BalanceForward.just_pressed(&bindings, &keyboard_input) {
             head_balance = 1.;
-        } else if GameControl::BalanceBackward.just_pressed(&keyboard_input) {
+        } else if GameControl::BalanceBackward.just_pressed(&bindings, &keyboard_input) {
             head_balance = -1.;
         }
         actions.head_balance = Some(head_balance);
@@ -72,11 +153,59 @@ fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<
         actions.head_balance = None;
     }
 
-    actions.jump = GameControl::Jump.just_pressed(&keyboard_input);
-    actions.restart = GameControl::Restart.just_pressed(&keyboard_input);
+    // kept until the next physics step, which only runs in the next frame
+    actions.jump |= GameControl::Jump.just_pressed(&bindings, &keyboard_input);
+    actions.restart |= GameControl::Restart.just_pressed(&bindings, &keyboard_input);
+
+    for gamepad in gamepads.0.iter() {
+        let axis = |axis_type| {
+            gamepad_axes
+                .get(GamepadAxis(*gamepad, axis_type))
+                .unwrap_or(0.)
+        };
+        let trigger = |button_type| {
+            gamepad_button_axes
+                .get(GamepadButton(*gamepad, button_type))
+                .unwrap_or(0.)
+        };
+        let paddling = shape_stick(axis(GamepadAxisType::LeftStickX), &settings);
+        let mut head_balance = shape_stick(axis(GamepadAxisType::RightStickX), &settings);
+        if head_balance == 0. {
+            head_balance = shape_stick(
+                trigger(GamepadButtonType::RightTrigger2)
+                    - trigger(GamepadButtonType::LeftTrigger2),
+                &settings,
+            );
+        }
+        // held keys win over the gamepad
+        if paddling != 0. && actions.paddling.unwrap_or(0.) == 0. {
+            actions.paddling = Some(paddling);
+        }
+        if head_balance != 0. && actions.head_balance.unwrap_or(0.) == 0. {
+            actions.head_balance = Some(head_balance);
+        }
+
+        let just_pressed =
+            |button_type| gamepad_buttons.just_pressed(GamepadButton(*gamepad, button_type));
+        actions.jump |= just_pressed(GamepadButtonType::South);
+        actions.restart |= just_pressed(GamepadButtonType::North);
+    }
 }
 
-enum GameControl {
+/// Apply deadzone and response curve to an axis value in `-1..=1`
+///
+/// The remaining range outside of the deadzone is stretched to start at zero again.
+fn shape_stick(value: f32, settings: &Settings) -> f32 {
+    let magnitude = value.abs().min(1.);
+    if magnitude <= settings.stick_deadzone {
+        return 0.;
+    }
+    let magnitude = (magnitude - settings.stick_deadzone) / (1. - settings.stick_deadzone);
+    magnitude.powf(settings.stick_response) * value.signum()
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum GameControl {
     BalanceForward,
     BalanceBackward,
     PaddleBackward,
@@ -85,37 +214,93 @@ enum GameControl {
     Jump,
 }
 
-impl GameControl {
-    fn just_released(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
-        match self {
-            GameControl::BalanceForward => keyboard_input.just_released(KeyCode::Right),
-            GameControl::BalanceBackward => keyboard_input.just_released(KeyCode::Left),
-            GameControl::PaddleBackward => keyboard_input.just_released(KeyCode::A),
-            GameControl::PaddleForward => keyboard_input.just_released(KeyCode::D),
-            GameControl::Jump => keyboard_input.just_released(KeyCode::Space),
-            GameControl::Restart => keyboard_input.just_released(KeyCode::R),
+/// The keys triggering each control
+///
+/// Every control can be bound to multiple keys, but a key belongs to at most one control.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KeyBindings(pub HashMap<GameControl, Vec<KeyCode>>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut bindings = HashMap::default();
+        bindings.insert(GameControl::BalanceForward, vec![KeyCode::Right]);
+        bindings.insert(GameControl::BalanceBackward, vec![KeyCode::Left]);
+        bindings.insert(GameControl::PaddleBackward, vec![KeyCode::A]);
+        bindings.insert(GameControl::PaddleForward, vec![KeyCode::D]);
+        bindings.insert(GameControl::Restart, vec![KeyCode::R]);
+        bindings.insert(GameControl::Jump, vec![KeyCode::Space]);
+        KeyBindings(bindings)
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, control: GameControl) -> &[KeyCode] {
+        self.0.get(&control).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The control other than `control` that is already bound to `key`
+    pub fn conflict(&self, control: GameControl, key: KeyCode) -> Option<GameControl> {
+        GameControl::ALL
+            .iter()
+            .copied()
+            .find(|other| *other != control && self.keys(*other).contains(&key))
+    }
+
+    /// Bind `key` to `control` in addition to the keys it already has
+    pub fn add(&mut self, control: GameControl, key: KeyCode) {
+        let keys = self.0.entry(control).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
         }
     }
 
-    fn pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
-        match self {
-            GameControl::BalanceForward => keyboard_input.pressed(KeyCode::Right),
-            GameControl::BalanceBackward => keyboard_input.pressed(KeyCode::Left),
-            GameControl::PaddleBackward => keyboard_input.pressed(KeyCode::A),
-            GameControl::PaddleForward => keyboard_input.pressed(KeyCode::D),
-            GameControl::Jump => keyboard_input.pressed(KeyCode::Space),
-            GameControl::Restart => keyboard_input.pressed(KeyCode::R),
+    /// Unbind `key` from `control`, keeping the other keys of the control
+    pub fn remove(&mut self, control: GameControl, key: KeyCode) {
+        if let Some(keys) = self.0.get_mut(&control) {
+            keys.retain(|bound| *bound != key);
         }
     }
+}
 
-    fn just_pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
+impl GameControl {
+    pub const ALL: [GameControl; 6] = [
+        GameControl::PaddleForward,
+        GameControl::PaddleBackward,
+        GameControl::BalanceForward,
+        GameControl::BalanceBackward,
+        GameControl::Jump,
+        GameControl::Restart,
+    ];
+
+    pub fn label(&self) -> &'static str {
         match self {
-            GameControl::BalanceForward => keyboard_input.just_pressed(KeyCode::Right),
-            GameControl::BalanceBackward => keyboard_input.just_pressed(KeyCode::Left),
-            GameControl::PaddleBackward => keyboard_input.just_pressed(KeyCode::A),
-            GameControl::PaddleForward => keyboard_input.just_pressed(KeyCode::D),
-            GameControl::Jump => keyboard_input.just_pressed(KeyCode::Space),
-            GameControl::Restart => keyboard_input.just_pressed(KeyCode::R),
+            GameControl::BalanceForward => "Lean forward",
+            GameControl::BalanceBackward => "Lean backward",
+            GameControl::PaddleBackward => "Paddle backward",
+            GameControl::PaddleForward => "Paddle forward",
+            GameControl::Restart => "Restart",
+            GameControl::Jump => "Jump",
         }
     }
+
+    pub fn just_released(&self, bindings: &KeyBindings, keyboard_input: &Input<KeyCode>) -> bool {
+        bindings
+            .keys(*self)
+            .iter()
+            .any(|key| keyboard_input.just_released(*key))
+    }
+
+    pub fn pressed(&self, bindings: &KeyBindings, keyboard_input: &Input<KeyCode>) -> bool {
+        bindings
+            .keys(*self)
+            .iter()
+            .any(|key| keyboard_input.pressed(*key))
+    }
+
+    pub fn just_pressed(&self, bindings: &KeyBindings, keyboard_input: &Input<KeyCode>) -> bool {
+        bindings
+            .keys(*self)
+            .iter()
+            .any(|key| keyboard_input.just_pressed(*key))
+    }
 }
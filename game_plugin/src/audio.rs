@@ -1,7 +1,8 @@
 use crate::loading::AudioAssets;
+use crate::settings::Settings;
 use crate::GameState;
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioPlugin};
+use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin, AudioSource};
 use rand::Rng;
 
 pub struct InternalAudioPlugin;
@@ -10,9 +11,11 @@ impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_plugin(AudioPlugin)
             .add_event::<PlaySoundEffect>()
+            .init_resource::<AudioChannels>()
             .add_system_set(
-                SystemSet::on_enter(GameState::Menu).with_system(start_background.system()),
+                SystemSet::on_exit(GameState::Loading).with_system(start_background.system()),
             )
+            .add_system(apply_volume_settings.system())
             .add_system_set(
                 SystemSet::on_update(GameState::InLevel).with_system(play_sound_effects.system()),
             )
@@ -25,6 +28,20 @@ impl Plugin for InternalAudioPlugin {
     }
 }
 
+struct AudioChannels {
+    music: AudioChannel,
+    effects: AudioChannel,
+}
+
+impl Default for AudioChannels {
+    fn default() -> Self {
+        AudioChannels {
+            music: AudioChannel::new("music".to_owned()),
+            effects: AudioChannel::new("effects".to_owned()),
+        }
+    }
+}
+
 pub enum PlaySoundEffect {
     Jump,
     Land,
@@ -36,31 +53,51 @@ pub enum PlaySoundEffect {
 fn play_sound_effects(
     audio_assets: Res<AudioAssets>,
     audio: Res<Audio>,
+    channels: Res<AudioChannels>,
     mut events: EventReader<PlaySoundEffect>,
 ) {
+    let play = |sound: &Handle<AudioSource>| {
+        audio.play_in_channel(sound.clone(), &channels.effects);
+    };
     for event in events.iter() {
         match event {
             PlaySoundEffect::Jump => match rand::thread_rng().gen_range(0..2) {
-                0 => audio.play(audio_assets.jump_1.clone()),
-                _ => audio.play(audio_assets.jump_2.clone()),
+                0 => play(&audio_assets.jump_1),
+                _ => play(&audio_assets.jump_2),
             },
             PlaySoundEffect::Land => {
-                audio.play(audio_assets.land_1.clone());
+                play(&audio_assets.land_1);
             }
             PlaySoundEffect::Fall => {
-                audio.play(audio_assets.fall.clone());
+                play(&audio_assets.fall);
             }
             PlaySoundEffect::Won => {
-                audio.play(audio_assets.won.clone());
+                play(&audio_assets.won);
             }
             PlaySoundEffect::Loose => match rand::thread_rng().gen_range(0..2) {
-                0 => audio.play(audio_assets.lose_1.clone()),
-                _ => audio.play(audio_assets.lose_2.clone()),
+                0 => play(&audio_assets.lose_1),
+                _ => play(&audio_assets.lose_2),
             },
         }
     }
 }
 
-fn start_background(audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
-    audio.play_looped(audio_assets.background.clone());
+/// The audio collection is missing if loading failed
+fn start_background(
+    audio_assets: Option<Res<AudioAssets>>,
+    audio: Res<Audio>,
+    channels: Res<AudioChannels>,
+) {
+    if let Some(audio_assets) = audio_assets {
+        audio.play_looped_in_channel(audio_assets.background.clone(), &channels.music);
+    }
+}
+
+fn apply_volume_settings(audio: Res<Audio>, channels: Res<AudioChannels>, settings: Res<Settings>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let volume = |enabled| if enabled { 1. } else { 0. };
+    audio.set_volume_in_channel(volume(settings.music), &channels.music);
+    audio.set_volume_in_channel(volume(settings.sound_effects), &channels.effects);
 }
@@ -0,0 +1,319 @@
+use crate::actions::{Actions, ActionsSystem, InputSource};
+use crate::levels::{level_order, Level, Obstacle, ObstacleShape};
+use crate::loading::LevelAssets;
+use crate::player::{Body, Wheel, BOULDER_HEIGTH, PHYSICS_SCALE, WHEEL_RADIUS};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub struct AutopilotPlugin;
+
+/// Seconds without any input on the title screen until the demo starts
+const DEMO_DELAY: f32 = 15.;
+/// Seconds the end of a demo run stays on screen
+const DEMO_END_DELAY: f32 = 3.;
+/// Speed in m/s the autopilot rides at
+const CRUISE_SPEED: f32 = 10.;
+/// Lean in radians the autopilot accepts to speed up or slow down
+const MAX_TARGET_LEAN: f32 = 0.15;
+/// Seconds before reaching the edge of a hole the autopilot jumps
+const HOLE_JUMP_LEAD: f32 = 0.15;
+/// Seconds before hitting an obstacle the autopilot jumps
+const OBSTACLE_JUMP_LEAD: f32 = 0.4;
+/// Distance in pixels to a takeoff point from which a jump is always fine, even when standing still
+const MIN_JUMP_REACH: f32 = 24.;
+/// Distance in pixels between the points the autopilot checks the ground at
+const GROUND_SAMPLE_STEP: f32 = 8.;
+/// Highest rise in pixels between two ground samples the wheel still rolls up
+const MAX_STEP_HEIGHT: f32 = 0.5 * WHEEL_RADIUS * PHYSICS_SCALE;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Demo>()
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel)
+                    .with_system(autopilot.system().after(ActionsSystem::SetMovementActions)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu).with_system(reset_demo_timer.system()),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(start_demo.system()))
+            // after all state changes of the frame, so they cannot clash with leaving the demo
+            .add_system_to_stage(CoreStage::PostUpdate, stop_demo.system());
+    }
+}
+
+/// The autopilot showing off a level on its own
+#[derive(Default)]
+struct Demo {
+    running: bool,
+    /// Seconds the title screen or the end of a demo run have been on screen
+    timer: f32,
+}
+
+/// What the autopilot knows about the rider, in physics units
+struct RiderState {
+    /// Horizontal position of the wheel
+    x: f32,
+    speed: f32,
+    /// Angle between the body and the vertical, positive when leaning forward
+    lean: f32,
+    lean_rate: f32,
+}
+
+/// Balance the rider while riding towards the finish line and jump over holes and obstacles
+///
+/// Paddling keeps the wheel underneath the leaning body, while pushing the head decides how far
+/// the rider leans. Leaning forward speeds the rider up and leaning back slows it down.
+fn autopilot(
+    source: Res<InputSource>,
+    level: Res<Level>,
+    mut actions: ResMut<Actions>,
+    wheel_query: Query<(&RigidBodyPosition, &RigidBodyVelocity), With<Wheel>>,
+    body_query: Query<(&RigidBodyPosition, &RigidBodyVelocity), With<Body>>,
+) {
+    if *source != InputSource::Autopilot {
+        return;
+    }
+    let rider = match (wheel_query.single(), body_query.single()) {
+        (Ok((wheel, wheel_velocity)), Ok((body, body_velocity))) => RiderState {
+            x: wheel.position.translation.x,
+            speed: wheel_velocity.linvel.x,
+            lean: -body.position.rotation.angle(),
+            lean_rate: -body_velocity.angvel,
+        },
+        _ => return,
+    };
+
+    let target_lean =
+        ((CRUISE_SPEED - rider.speed) * 0.05).clamp(-MAX_TARGET_LEAN, MAX_TARGET_LEAN);
+    let paddling = rider.lean * 4. + rider.lean_rate * 0.8;
+    let head_balance = (target_lean - rider.lean) * 3. - rider.lean_rate * 0.5;
+    actions.paddling = Some(paddling.clamp(-1., 1.));
+    actions.head_balance = Some(head_balance.clamp(-1., 1.));
+    actions.jump = should_jump(&level, &rider);
+    actions.restart = false;
+}
+
+/// Jump when the wheel is about to reach the edge of a hole or hit an obstacle
+fn should_jump(level: &Level, rider: &RiderState) -> bool {
+    let x = rider.x * PHYSICS_SCALE;
+    let speed = rider.speed.max(0.) * PHYSICS_SCALE;
+    takeoff_points(level)
+        .iter()
+        .any(|(takeoff, lead)| *takeoff > x && *takeoff - x < speed * lead + MIN_JUMP_REACH)
+}
+
+/// Horizontal positions in pixels the rider has to jump from, with the time to jump ahead of them
+///
+/// That is the edge of every hole not filled up by terrain, or the top of an obstacle reaching over
+/// it, the side of every flat obstacle and every step in the ground too high to roll up. Ramps are
+/// ridden up instead.
+fn takeoff_points(level: &Level) -> Vec<(f32, f32)> {
+    let outlines: Vec<ObstacleOutline> = level.obstacles.iter().map(ObstacleOutline::new).collect();
+    let covered = |x: f32| {
+        level
+            .terrain
+            .iter()
+            .any(|terrain| terrain.height_at(x).is_some())
+    };
+    let mut points: Vec<(f32, f32)> = level
+        .holes
+        .iter()
+        .filter_map(|hole| {
+            // the ground goes on as long as terrain covers the hole
+            let open = samples(hole[0], hole[1]).find(|x| !covered(*x))?;
+            let ground_end = (open - GROUND_SAMPLE_STEP).max(hole[0]);
+            let edge = outlines
+                .iter()
+                .filter(|outline| outline.start <= ground_end && outline.end >= ground_end)
+                .fold(ground_end, |edge, outline| edge.max(outline.top));
+            Some((edge, HOLE_JUMP_LEAD))
+        })
+        .collect();
+    points.extend(
+        outlines
+            .iter()
+            .filter(|outline| !outline.ramp)
+            .map(|outline| (outline.start, OBSTACLE_JUMP_LEAD)),
+    );
+    points.extend(
+        terrain_steps(level)
+            .into_iter()
+            .map(|step| (step, OBSTACLE_JUMP_LEAD)),
+    );
+    points
+}
+
+/// Positions in pixels right in front of the ground rising too steeply to roll up
+fn terrain_steps(level: &Level) -> Vec<f32> {
+    if level.terrain.is_empty() {
+        return vec![];
+    }
+    let segments = level.ground_segments();
+    let mut steps = vec![];
+    let mut last: Option<(f32, f32)> = None;
+    for x in samples(level.ground[0], level.ground[1]) {
+        // the wheel rides on the highest ground
+        let height = level
+            .terrain
+            .iter()
+            .filter_map(|terrain| terrain.height_at(x))
+            .chain(
+                segments
+                    .iter()
+                    .filter(|[start, end]| *start <= x && x <= *end)
+                    .map(|_| BOULDER_HEIGTH * PHYSICS_SCALE),
+            )
+            .fold(None, |highest: Option<f32>, height| {
+                Some(highest.map_or(height, |highest| highest.max(height)))
+            });
+        if let (Some((last_x, last_height)), Some(height)) = (last, height) {
+            if height - last_height > MAX_STEP_HEIGHT {
+                steps.push(last_x);
+            }
+        }
+        last = height.map(|height| (x, height));
+    }
+    steps
+}
+
+/// Points from `start` to `end`, [`GROUND_SAMPLE_STEP`] apart
+fn samples(start: f32, end: f32) -> impl Iterator<Item = f32> {
+    let count = ((end - start) / GROUND_SAMPLE_STEP).max(0.) as usize;
+    (0..=count).map(move |index| start + index as f32 * GROUND_SAMPLE_STEP)
+}
+
+/// Horizontal extent of an obstacle in pixels
+struct ObstacleOutline {
+    start: f32,
+    end: f32,
+    /// Position of the highest point. The rightmost one, if there are several.
+    top: f32,
+    ramp: bool,
+}
+
+impl ObstacleOutline {
+    fn new(obstacle: &Obstacle) -> Self {
+        let [x, y] = obstacle.position;
+        match obstacle.shape {
+            ObstacleShape::Cuboid {
+                half_width,
+                half_height,
+            } => {
+                let rotation = Vec2::new(obstacle.rotation.cos(), obstacle.rotation.sin());
+                let corners: Vec<Vec2> = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+                    .iter()
+                    .map(|(sx, sy)| {
+                        let corner = Vec2::new(sx * half_width, sy * half_height);
+                        Vec2::new(
+                            x + corner.x * rotation.x - corner.y * rotation.y,
+                            y + corner.x * rotation.y + corner.y * rotation.x,
+                        )
+                    })
+                    .collect();
+                let top = corners
+                    .iter()
+                    .fold(corners[0], |top, corner| {
+                        if corner.y > top.y + 0.5 || (corner.y > top.y - 0.5 && corner.x > top.x) {
+                            *corner
+                        } else {
+                            top
+                        }
+                    })
+                    .x;
+                ObstacleOutline {
+                    start: corners
+                        .iter()
+                        .map(|corner| corner.x)
+                        .fold(f32::MAX, f32::min),
+                    end: corners
+                        .iter()
+                        .map(|corner| corner.x)
+                        .fold(f32::MIN, f32::max),
+                    top,
+                    ramp: obstacle.rotation.sin().abs() > 0.1,
+                }
+            }
+            ObstacleShape::Ball { radius } => ObstacleOutline {
+                start: x - radius,
+                end: x + radius,
+                top: x,
+                ramp: false,
+            },
+        }
+    }
+}
+
+fn reset_demo_timer(mut demo: ResMut<Demo>) {
+    demo.timer = 0.;
+}
+
+fn any_input(
+    keyboard_input: &Input<KeyCode>,
+    mouse_input: &Input<MouseButton>,
+    gamepad_buttons: &Input<GamepadButton>,
+) -> bool {
+    keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+        || gamepad_buttons.get_just_pressed().next().is_some()
+}
+
+/// Let the autopilot ride the first level after the title screen was left alone for a while
+fn start_demo(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    mut demo: ResMut<Demo>,
+    mut level: ResMut<Level>,
+    mut source: ResMut<InputSource>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if *source != InputSource::Player {
+        return;
+    }
+    if any_input(&keyboard_input, &mouse_input, &gamepad_buttons) {
+        demo.timer = 0.;
+        return;
+    }
+    demo.timer += time.delta_seconds();
+    if demo.timer < DEMO_DELAY {
+        return;
+    }
+    if let Some(first) = level_order(&level_assets, &levels).first() {
+        *level = (*first).clone();
+        *source = InputSource::Autopilot;
+        *demo = Demo {
+            running: true,
+            timer: 0.,
+        };
+        state.set(GameState::Prepare).unwrap();
+    }
+}
+
+/// Any input or the end of the demo run returns to the title screen
+fn stop_demo(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut demo: ResMut<Demo>,
+    mut source: ResMut<InputSource>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !demo.running {
+        return;
+    }
+    // the run is over
+    if state.current() != &GameState::InLevel {
+        demo.timer += time.delta_seconds();
+    }
+    if demo.timer > DEMO_END_DELAY || any_input(&keyboard_input, &mouse_input, &gamepad_buttons) {
+        demo.running = false;
+        *source = InputSource::Player;
+        state.replace(GameState::Menu).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use crate::camera::CameraSystem;
+use crate::levels::{ForLevel, Level};
+use crate::loading::TextureAssets;
+use crate::player::Camera;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct BackgroundPlugin;
+
+/// Widest view in pixels the background layers have enough tiles for
+const MAX_VIEW_WIDTH: f32 = 3840.;
+/// Depth of the farthest layer. Everything else in a level is drawn at zero.
+const BACKGROUND_DEPTH: f32 = -0.05;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::PrepareLevel).with_system(spawn_layers.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::InLevel)
+                .with_system(scroll_layers.system().after(CameraSystem::Follow)),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Lost)
+                .with_system(scroll_layers.system().after(CameraSystem::Follow)),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Finished)
+                .with_system(scroll_layers.system().after(CameraSystem::Follow)),
+        );
+    }
+}
+
+/// One layer of the background, repeated endlessly to both sides
+///
+/// The first layer of a level or theme is the farthest away. Sizes are given in pixels.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackgroundLayer {
+    /// Tiles of the layer, repeated in this order
+    pub textures: Vec<BackgroundTexture>,
+    /// How fast the layer moves with the world. 1 sticks to the ground, 0 stays with the camera.
+    pub scroll: f32,
+    /// Height of the layer's center when the camera is at zero
+    pub offset: f32,
+    /// Distance between two tiles
+    pub tile_width: f32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum BackgroundTexture {
+    Background1,
+    Background2,
+    Background3,
+}
+
+impl BackgroundTexture {
+    pub fn texture(&self, textures: &TextureAssets) -> Handle<Texture> {
+        match self {
+            BackgroundTexture::Background1 => textures.background_1.clone(),
+            BackgroundTexture::Background2 => textures.background_2.clone(),
+            BackgroundTexture::Background3 => textures.background_3.clone(),
+        }
+    }
+}
+
+/// A sprite of a background layer that is moved along with the camera
+struct BackgroundTile {
+    layer: BackgroundLayer,
+    /// Position of the tile among the tiles of its layer
+    slot: i32,
+    /// One material per texture of the layer
+    materials: Vec<Handle<ColorMaterial>>,
+}
+
+fn spawn_layers(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
+) {
+    let layers = level
+        .backgrounds
+        .clone()
+        .unwrap_or_else(|| level.theme.backgrounds());
+    for (index, layer) in layers.into_iter().enumerate() {
+        if layer.textures.is_empty() || layer.tile_width <= 0. {
+            warn!("Skipping background layer {} without tiles", index);
+            continue;
+        }
+        let layer_materials: Vec<Handle<ColorMaterial>> = layer
+            .textures
+            .iter()
+            .map(|texture| materials.add(texture.texture(&textures).into()))
+            .collect();
+        let tiles = (MAX_VIEW_WIDTH / layer.tile_width).ceil() as i32 + 2;
+        let depth = BACKGROUND_DEPTH + 0.001 * index as f32;
+        for slot in 0..tiles {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: layer_materials[0].clone(),
+                    transform: Transform::from_translation(Vec3::new(0., layer.offset, depth)),
+                    ..Default::default()
+                })
+                .insert(BackgroundTile {
+                    layer: layer.clone(),
+                    slot,
+                    materials: layer_materials.clone(),
+                })
+                .insert(ForLevel);
+        }
+    }
+}
+
+/// Place the tiles of every layer around the camera, so they cover the view at any position
+fn scroll_layers(
+    camera_query: Query<&Transform, (With<Camera>, Without<BackgroundTile>)>,
+    mut tile_query: Query<(&BackgroundTile, &mut Transform, &mut Handle<ColorMaterial>)>,
+) {
+    let camera = match camera_query.single() {
+        Ok(camera) => camera.translation,
+        Err(_) => return,
+    };
+    for (tile, mut transform, mut material) in tile_query.iter_mut() {
+        let layer = &tile.layer;
+        // position of the layer's tile zero
+        let origin = camera.x * (1. - layer.scroll);
+        let first = ((camera.x - 0.5 * MAX_VIEW_WIDTH - origin) / layer.tile_width).floor() as i32;
+        let index = first + tile.slot;
+        transform.translation.x = origin + index as f32 * layer.tile_width;
+        transform.translation.y = layer.offset + camera.y * (1. - layer.scroll);
+        let texture = index.rem_euclid(tile.materials.len() as i32) as usize;
+        if *material != tile.materials[texture] {
+            *material = tile.materials[texture].clone();
+        }
+    }
+}
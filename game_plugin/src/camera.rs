@@ -0,0 +1,306 @@
+use crate::levels::Level;
+use crate::player::{Body, Camera, Head, PHYSICS_SCALE};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::render::camera::OrthographicProjection;
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub struct CameraPlugin;
+
+/// Landings slower than this in m/s don't shake the camera
+const MIN_SHAKING_LANDING: f32 = 6.;
+/// Landings at this speed in m/s or faster shake the camera the most
+const MAX_SHAKING_LANDING: f32 = 20.;
+/// Trauma lost per second. The shake is gone a second after the strongest impact.
+const TRAUMA_DECAY: f32 = 1.;
+/// Distance in pixels the camera is pushed off at full trauma
+const MAX_SHAKE: f32 = 24.;
+/// Seconds the physics freeze when crashing with the head
+const HIT_STOP: f32 = 0.12;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CameraFollow>()
+            .init_resource::<CameraShake>()
+            .init_resource::<HitStop>()
+            .add_event::<Impact>()
+            .add_system(react_to_impacts.system())
+            .add_system(tick_hit_stop.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::PrepareLevel).with_system(end_hit_stop.system()),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Lost).with_system(end_hit_stop.system()))
+            // the shake is only added for rendering, so everything moving the camera during the
+            // frame sees where it actually is
+            .add_system_to_stage(CoreStage::PreUpdate, remove_shake.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                shake_camera
+                    .system()
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::InLevel).with_system(snap_camera.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel)
+                    .with_system(follow_rider.system().label(CameraSystem::Follow)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Lost)
+                    .with_system(follow_rider.system().label(CameraSystem::Follow)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Finished)
+                    .with_system(follow_rider.system().label(CameraSystem::Follow)),
+            );
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum CameraSystem {
+    /// Moves the camera along with the rider
+    Follow,
+}
+
+/// How the camera follows the rider through a level. Distances are given in pixels.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Roughly the seconds the camera needs to catch up with the rider
+    pub smoothing: f32,
+    /// Seconds of the rider's current speed the camera looks ahead
+    pub lookahead: f32,
+    pub max_lookahead: f32,
+    /// The camera only follows the head vertically once it leaves this distance above or below
+    pub deadzone: f32,
+    /// Position of the view's center relative to the head
+    pub offset: [f32; 2],
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            smoothing: 0.3,
+            lookahead: 0.4,
+            max_lookahead: 200.,
+            deadzone: 64.,
+            offset: [0., 140.],
+        }
+    }
+}
+
+/// Something the rider ran into, to be felt through the camera
+pub enum Impact {
+    /// The wheel touched down with the given downwards speed in m/s
+    Landing(f32),
+    /// The head hit a platform
+    Crash,
+    /// The rider fell into a hole
+    Fall,
+}
+
+/// Shaking the camera after impacts. The shake grows with the square of the trauma.
+#[derive(Default)]
+struct CameraShake {
+    /// From 0 for no shake to 1 for the strongest one
+    trauma: f32,
+    /// Offset currently added to the camera's position
+    offset: Vec2,
+}
+
+/// Physics frozen for a moment after a crash, to make it hit harder
+#[derive(Default)]
+struct HitStop {
+    remaining: Option<f32>,
+}
+
+/// State of the camera following the rider
+#[derive(Default)]
+struct CameraFollow {
+    velocity: Vec2,
+    /// Height the camera is looking at, only moved when the head leaves the deadzone around it
+    focus: f32,
+}
+
+/// Start a level with the camera right at the rider instead of sweeping over from elsewhere
+fn snap_camera(
+    level: Res<Level>,
+    mut follow: ResMut<CameraFollow>,
+    head_query: Query<&Transform, (With<Head>, Without<Camera>)>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let head = match head_query.single() {
+        Ok(head) => head.translation,
+        Err(_) => return,
+    };
+    *follow = CameraFollow {
+        velocity: Vec2::ZERO,
+        focus: head.y,
+    };
+    let settings = &level.camera;
+    for (mut camera_transform, projection) in camera_query.iter_mut() {
+        let target = Vec2::new(head.x + settings.offset[0], head.y + settings.offset[1]);
+        let target = clamp_to_level(target, &level, projection);
+        camera_transform.translation.x = target.x;
+        camera_transform.translation.y = target.y;
+    }
+}
+
+/// Smoothly follow the rider's head, looking ahead in the direction it moves
+fn follow_rider(
+    time: Res<Time>,
+    level: Res<Level>,
+    mut follow: ResMut<CameraFollow>,
+    head_query: Query<&Transform, (With<Head>, Without<Camera>)>,
+    body_query: Query<&RigidBodyVelocity, With<Body>>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let (head, velocity) = match (head_query.single(), body_query.single()) {
+        (Ok(head), Ok(velocity)) => (head.translation, velocity.linvel.x * PHYSICS_SCALE),
+        _ => return,
+    };
+    let settings = &level.camera;
+    follow.focus = follow
+        .focus
+        .clamp(head.y - settings.deadzone, head.y + settings.deadzone);
+    let lookahead =
+        (velocity * settings.lookahead).clamp(-settings.max_lookahead, settings.max_lookahead);
+    let target = Vec2::new(
+        head.x + lookahead + settings.offset[0],
+        follow.focus + settings.offset[1],
+    );
+    for (mut camera_transform, projection) in camera_query.iter_mut() {
+        let target = clamp_to_level(target, &level, projection);
+        let position = smooth_damp(
+            camera_transform.translation.truncate(),
+            target,
+            &mut follow.velocity,
+            settings.smoothing,
+            time.delta_seconds(),
+        );
+        camera_transform.translation.x = position.x;
+        camera_transform.translation.y = position.y;
+    }
+}
+
+/// Keep the view in between the wall on the left and the end of the level on the right
+fn clamp_to_level(target: Vec2, level: &Level, projection: &OrthographicProjection) -> Vec2 {
+    let half_width = 0.5 * (projection.right - projection.left) * projection.scale;
+    let left = level.ground[0] + half_width;
+    let right = if level.endless {
+        f32::INFINITY
+    } else {
+        level.ground[1] - half_width
+    };
+    let x = if left > right {
+        // the whole level fits into the view
+        0.5 * (level.ground[0] + level.ground[1])
+    } else {
+        target.x.clamp(left, right)
+    };
+    Vec2::new(x, target.y)
+}
+
+/// Move towards the target like a critically damped spring, so the camera never overshoots
+fn smooth_damp(
+    current: Vec2,
+    target: Vec2,
+    velocity: &mut Vec2,
+    smoothing: f32,
+    delta: f32,
+) -> Vec2 {
+    let omega = 2. / smoothing.max(0.0001);
+    let x = omega * delta;
+    let decay = 1. / (1. + x + 0.48 * x * x + 0.235 * x * x * x);
+    let change = current - target;
+    let temp = (*velocity + omega * change) * delta;
+    *velocity = (*velocity - omega * temp) * decay;
+    target + (change + temp) * decay
+}
+
+fn react_to_impacts(
+    mut impacts: EventReader<Impact>,
+    settings: Res<Settings>,
+    mut shake: ResMut<CameraShake>,
+    mut hit_stop: ResMut<HitStop>,
+    mut configuration: ResMut<RapierConfiguration>,
+) {
+    for impact in impacts.iter() {
+        let trauma = match impact {
+            Impact::Landing(speed) => {
+                ((speed - MIN_SHAKING_LANDING) / (MAX_SHAKING_LANDING - MIN_SHAKING_LANDING))
+                    .clamp(0., 1.)
+                    * 0.6
+            }
+            Impact::Crash => {
+                hit_stop.remaining = Some(HIT_STOP);
+                configuration.physics_pipeline_active = false;
+                0.8
+            }
+            Impact::Fall => 0.5,
+        };
+        if settings.screen_shake {
+            shake.trauma = (shake.trauma + trauma).min(1.);
+        }
+    }
+}
+
+fn tick_hit_stop(
+    time: Res<Time>,
+    mut hit_stop: ResMut<HitStop>,
+    configuration: ResMut<RapierConfiguration>,
+) {
+    if let Some(remaining) = hit_stop.remaining.as_mut() {
+        *remaining -= time.delta_seconds();
+        if *remaining <= 0. {
+            end_hit_stop(hit_stop, configuration);
+        }
+    }
+}
+
+fn end_hit_stop(mut hit_stop: ResMut<HitStop>, mut configuration: ResMut<RapierConfiguration>) {
+    if hit_stop.remaining.take().is_some() {
+        configuration.physics_pipeline_active = true;
+    }
+}
+
+fn remove_shake(
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation.x -= shake.offset.x;
+        camera_transform.translation.y -= shake.offset.y;
+    }
+    shake.offset = Vec2::ZERO;
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    if !settings.screen_shake {
+        shake.trauma = 0.;
+    }
+    if shake.trauma <= 0. {
+        return;
+    }
+    let mut random = rand::thread_rng();
+    let strength = MAX_SHAKE * shake.trauma * shake.trauma;
+    shake.offset = Vec2::new(
+        random.gen_range(-1. ..1.) * strength,
+        random.gen_range(-1. ..1.) * strength,
+    );
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.);
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation.x += shake.offset.x;
+        camera_transform.translation.y += shake.offset.y;
+    }
+}
@@ -0,0 +1,178 @@
+use crate::actions::{ConnectedGamepads, GameControl, KeyBindings};
+use crate::loading::FontAssets;
+use crate::lost::ButtonMaterials;
+use crate::menu::{menu_text, spawn_menu, ButtonActivated, MenuFocus};
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Rebinding>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Controls).with_system(show_controls.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Controls)
+                    .with_system(rebind.system())
+                    .with_system(update_labels.system()),
+            );
+    }
+}
+
+/// The control waiting for a new key and feedback for the player
+#[derive(Default)]
+struct Rebinding {
+    control: Option<GameControl>,
+    message: String,
+}
+
+#[derive(Clone, Copy)]
+enum ControlsButton {
+    Control(GameControl),
+    Reset,
+    Back,
+}
+
+struct StatusText;
+
+impl ControlsButton {
+    fn label(&self, bindings: &KeyBindings, rebinding: &Rebinding) -> String {
+        match self {
+            ControlsButton::Control(control) if rebinding.control == Some(*control) => {
+                format!("{}: ...", control.label())
+            }
+            ControlsButton::Control(control) => {
+                let keys: Vec<String> = bindings
+                    .keys(*control)
+                    .iter()
+                    .map(|key| format!("{:?}", key))
+                    .collect();
+                format!("{}: {}", control.label(), keys.join(", "))
+            }
+            ControlsButton::Reset => "Reset to defaults".to_string(),
+            ControlsButton::Back => "Back".to_string(),
+        }
+    }
+}
+
+fn show_controls(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    bindings: Res<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    *rebinding = Rebinding::default();
+    let mut buttons: Vec<ControlsButton> = GameControl::ALL
+        .iter()
+        .map(|control| ControlsButton::Control(*control))
+        .collect();
+    buttons.push(ControlsButton::Reset);
+    buttons.push(ControlsButton::Back);
+    let menu = spawn_menu(
+        &mut commands,
+        &font_assets,
+        &button_materials,
+        "Controls",
+        buttons
+            .iter()
+            .map(|button| (button.label(&bindings, &rebinding), *button))
+            .collect(),
+    );
+    commands.entity(menu).with_children(|parent| {
+        parent
+            .spawn_bundle(menu_text(&font_assets, "", 25.0))
+            .insert(StatusText);
+    });
+}
+
+/// Start rebinding a control when its button is activated. The next pressed key is added to
+/// the control, or removed from it if it was bound already.
+///
+/// A key that already belongs to another control is rejected, and the last key of a control
+/// can not be removed.
+fn rebind(
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<&ControlsButton>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<ConnectedGamepads>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut focus: ResMut<MenuFocus>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if let Some(control) = rebinding.control {
+        if let Some(key) = keyboard_input.get_just_pressed().next().copied() {
+            let keys = bindings.keys(control);
+            if key == KeyCode::Escape {
+                rebinding.message = String::new();
+            } else if let Some(other) = bindings.conflict(control, key) {
+                rebinding.message = format!("{:?} is already used for {}", key, other.label());
+                return;
+            } else if keys == [key] {
+                rebinding.message = format!("{} needs at least one key", control.label());
+                return;
+            } else if keys.contains(&key) {
+                bindings.remove(control, key);
+                rebinding.message = String::new();
+            } else {
+                bindings.add(control, key);
+                rebinding.message = String::new();
+            }
+            rebinding.control = None;
+            focus.locked = false;
+        }
+        return;
+    }
+
+    let back = keyboard_input.just_pressed(KeyCode::Escape)
+        || gamepads.0.iter().any(|gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton(*gamepad, GamepadButtonType::East))
+        });
+    if back {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(GameState::Settings).unwrap();
+        return;
+    }
+    for ButtonActivated(entity) in activated.iter() {
+        match buttons.get(*entity) {
+            Ok(ControlsButton::Control(control)) => {
+                rebinding.control = Some(*control);
+                rebinding.message = format!(
+                    "Press a key to add to or remove from {} or Escape to cancel",
+                    control.label().to_lowercase()
+                );
+                focus.locked = true;
+            }
+            Ok(ControlsButton::Reset) => {
+                *bindings = KeyBindings::default();
+                rebinding.message = String::new();
+            }
+            Ok(ControlsButton::Back) => state.set(GameState::Settings).unwrap(),
+            Err(_) => (),
+        }
+    }
+}
+
+fn update_labels(
+    bindings: Res<KeyBindings>,
+    rebinding: Res<Rebinding>,
+    buttons: Query<(&ControlsButton, &Children)>,
+    mut text_query: Query<&mut Text, Without<StatusText>>,
+    mut status_query: Query<&mut Text, With<StatusText>>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (button, children) in buttons.iter() {
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            text.sections[0].value = button.label(&bindings, &rebinding);
+        }
+    }
+    for mut text in status_query.iter_mut() {
+        text.sections[0].value = rebinding.message.clone();
+    }
+}
@@ -0,0 +1,664 @@
+use crate::generator::generate_level;
+use crate::levels::{Level, Obstacle, ObstacleShape, CUSTOM_LEVEL_PREFIX};
+use crate::loading::{FontAssets, TextureAssets};
+use crate::player::{Camera, BOULDER_HEIGTH, PHYSICS_SCALE};
+use crate::save::{read_data, write_data};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier2d::na::Point2;
+use bevy_rapier2d::prelude::*;
+use nalgebra::Isometry2;
+
+pub struct EditorPlugin;
+
+/// The ground always reaches this far behind the finish line
+const GROUND_BEHIND_FINISH: f32 = 400.;
+const ROTATION_STEP: f32 = std::f32::consts::PI / 12.;
+const NEW_HOLE_WIDTH: f32 = 200.;
+const CAMERA_SPEED: f32 = 600.;
+const GENERATOR_DIFFICULTY: f32 = 0.5;
+/// Any seed with this many digits fits into a `u64`
+const MAX_SEED_DIGITS: usize = 19;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<EditorSelection>()
+            .init_resource::<SeedInput>()
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel).with_system(open_editor.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Editor).with_system(show_editor_help.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .label(EditorSystem::SeedInput)
+                    .with_system(generate.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .after(EditorSystem::SeedInput)
+                    .with_system(select_and_drag.system())
+                    .with_system(edit_selection.system())
+                    .with_system(place_elements.system())
+                    .with_system(move_editor_camera.system())
+                    .with_system(save_and_reload.system())
+                    .with_system(test_ride.system())
+                    .with_system(draw_level.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Editor).with_system(close_editor.system()),
+            );
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+enum EditorSystem {
+    /// Takes the keys typed into a seed before the other editor systems see them
+    SeedInput,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Element {
+    Obstacle(usize),
+    Hole(usize),
+    Start,
+    FinishLine,
+}
+
+#[derive(Default)]
+struct EditorSelection {
+    element: Option<Element>,
+    dragging: bool,
+    /// Offset between the cursor and the dragged element
+    drag_offset: Vec2,
+}
+
+/// Digits of the seed being typed in after pressing Ctrl+G
+#[derive(Default)]
+struct SeedInput(Option<String>);
+
+struct EditorView;
+
+struct EditorUi;
+
+fn open_editor(
+    mut state: ResMut<State<GameState>>,
+    input: Res<Input<KeyCode>>,
+    mut level: ResMut<Level>,
+) {
+    if input.just_pressed(KeyCode::F1) && !level.endless {
+        make_custom(&mut level);
+        state.set(GameState::Editor).unwrap();
+    }
+}
+
+/// Edits never change the level they started from, neither its progress nor its file
+fn make_custom(level: &mut Level) {
+    if !level.is_custom() {
+        level.id = format!("{}{}", CUSTOM_LEVEL_PREFIX, level.id);
+    }
+}
+
+fn test_ride(mut state: ResMut<State<GameState>>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Return) {
+        state.set(GameState::PrepareLevel).unwrap();
+    }
+}
+
+fn close_editor(
+    mut commands: Commands,
+    mut selection: ResMut<EditorSelection>,
+    mut seed_input: ResMut<SeedInput>,
+    editor_entities: Query<Entity, Or<(With<EditorView>, With<EditorUi>)>>,
+) {
+    *selection = EditorSelection::default();
+    *seed_input = SeedInput::default();
+    for entity in editor_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn show_editor_help(mut commands: Commands, font_assets: Res<FontAssets>) {
+    let style = TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size: 16.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "Click + drag: select/move | Q/E: rotate | Del: delete\n\
+                            1: platform | 2: ramp | 3: hole | S: start | F: finish line\n\
+                            G: generate | Ctrl+G: generate from seed | A/D: scroll\n\
+                            Enter: test ride | Ctrl+S: save | Ctrl+L: reload"
+                            .to_string(),
+                        style: style.clone(),
+                    },
+                    // shows the seed while it is typed in
+                    TextSection {
+                        value: String::new(),
+                        style,
+                    },
+                ],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(EditorUi);
+}
+
+fn cursor_position(windows: &Windows, camera: &Transform) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+
+    Some(cursor - window_size / 2. + camera.translation.truncate())
+}
+
+fn element_at(level: &Level, position: Vec2) -> Option<Element> {
+    if (position.x - level.finish_line).abs() < 16. {
+        return Some(Element::FinishLine);
+    }
+    let start = &level.start;
+    if (position.x - start.wheel[0]).abs() < 32.
+        && position.y > start.wheel[1] - 32.
+        && position.y < start.head[1] + 32.
+    {
+        return Some(Element::Start);
+    }
+    for (index, obstacle) in level.obstacles.iter().enumerate() {
+        let local = Vec2::new(
+            position.x - obstacle.position[0],
+            position.y - obstacle.position[1],
+        );
+        let (sin, cos) = (-obstacle.rotation).sin_cos();
+        let local = Vec2::new(local.x * cos - local.y * sin, local.x * sin + local.y * cos);
+        let hit = match obstacle.shape {
+            ObstacleShape::Cuboid {
+                half_width,
+                half_height,
+            } => local.x.abs() < half_width && local.y.abs() < half_height,
+            ObstacleShape::Ball { radius } => local.length() < radius,
+        };
+        if hit {
+            return Some(Element::Obstacle(index));
+        }
+    }
+    let ground_height = BOULDER_HEIGTH * PHYSICS_SCALE;
+    if position.y.abs() < ground_height {
+        for (index, hole) in level.holes.iter().enumerate() {
+            if position.x > hole[0] && position.x < hole[1] {
+                return Some(Element::Hole(index));
+            }
+        }
+    }
+
+    None
+}
+
+fn element_position(level: &Level, element: Element) -> Vec2 {
+    match element {
+        Element::Obstacle(index) => level.obstacles[index].position.into(),
+        Element::Hole(index) => Vec2::new(level.holes[index][0], 0.),
+        Element::Start => level.start.wheel.into(),
+        Element::FinishLine => Vec2::new(level.finish_line, 0.),
+    }
+}
+
+fn move_element(level: &mut Level, element: Element, position: Vec2) {
+    match element {
+        Element::Obstacle(index) => level.obstacles[index].position = position.into(),
+        Element::Hole(index) => {
+            // holes stop at the ground's ends and their neighbours, so they stay in order
+            let left = match index {
+                0 => level.ground[0],
+                _ => level.holes[index - 1][1],
+            };
+            let right = match level.holes.get(index + 1) {
+                Some(next) => next[0],
+                None => level.ground[1],
+            };
+            let hole = &mut level.holes[index];
+            let width = hole[1] - hole[0];
+            let start = position.x.min(right - width).max(left);
+            *hole = [start, (start + width).min(right)];
+        }
+        Element::Start => {
+            // the start stays on the ground in front of the finish line
+            let x = position.x.min(level.finish_line).max(level.ground[0]);
+            let start = &mut level.start;
+            let offset = Vec2::new(x, position.y) - Vec2::from(start.wheel);
+            for point in [&mut start.wheel, &mut start.body, &mut start.head] {
+                *point = (Vec2::from(*point) + offset).into();
+            }
+        }
+        Element::FinishLine => {
+            // the finish line stays behind the start
+            let x = position.x.max(level.start.wheel[0]).max(level.ground[0]);
+            level.finish_line = x;
+            level.ground[1] = x + GROUND_BEHIND_FINISH;
+            let ground_end = level.ground[1];
+            level.holes.retain(|hole| hole[0] < ground_end);
+            for hole in level.holes.iter_mut() {
+                hole[1] = hole[1].min(ground_end);
+            }
+        }
+    }
+}
+
+/// Add a hole within the ground, merged with all holes it overlaps. Returns its index.
+fn add_hole(level: &mut Level, hole: [f32; 2]) -> Option<usize> {
+    let mut hole = [hole[0].max(level.ground[0]), hole[1].min(level.ground[1])];
+    if hole[0] >= hole[1] {
+        return None;
+    }
+    level.holes.retain(|other| {
+        let overlaps = other[0] <= hole[1] && other[1] >= hole[0];
+        if overlaps {
+            hole = [hole[0].min(other[0]), hole[1].max(other[1])];
+        }
+        !overlaps
+    });
+    let index = level
+        .holes
+        .iter()
+        .position(|other| other[0] > hole[0])
+        .unwrap_or(level.holes.len());
+    level.holes.insert(index, hole);
+    Some(index)
+}
+
+fn select_and_drag(
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    camera_query: Query<&Transform, With<Camera>>,
+    mut selection: ResMut<EditorSelection>,
+    mut level: ResMut<Level>,
+) {
+    let camera = camera_query.single().unwrap();
+    let cursor = match cursor_position(&windows, camera) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    if mouse_input.just_pressed(MouseButton::Left) {
+        let element = element_at(&level, cursor);
+        selection.element = element;
+        selection.dragging = element.is_some();
+        if let Some(element) = element {
+            selection.drag_offset = cursor - element_position(&level, element);
+        }
+    } else if mouse_input.just_released(MouseButton::Left) && selection.dragging {
+        selection.dragging = false;
+    } else if mouse_input.pressed(MouseButton::Left) && selection.dragging {
+        if let Some(element) = selection.element {
+            let target = cursor - selection.drag_offset;
+            if target != element_position(&level, element) {
+                move_element(&mut level, element, target);
+            }
+        }
+    }
+}
+
+fn edit_selection(
+    input: Res<Input<KeyCode>>,
+    mut selection: ResMut<EditorSelection>,
+    mut level: ResMut<Level>,
+) {
+    let element = match selection.element {
+        Some(element) => element,
+        None => return,
+    };
+    if input.just_pressed(KeyCode::Delete) || input.just_pressed(KeyCode::Back) {
+        match element {
+            Element::Obstacle(index) => {
+                level.obstacles.remove(index);
+            }
+            Element::Hole(index) => {
+                level.holes.remove(index);
+            }
+            Element::Start | Element::FinishLine => return,
+        }
+        *selection = EditorSelection::default();
+        return;
+    }
+    if let Element::Obstacle(index) = element {
+        if input.just_pressed(KeyCode::Q) {
+            level.obstacles[index].rotation += ROTATION_STEP;
+        } else if input.just_pressed(KeyCode::E) {
+            level.obstacles[index].rotation -= ROTATION_STEP;
+        }
+    }
+}
+
+fn place_elements(
+    windows: Res<Windows>,
+    input: Res<Input<KeyCode>>,
+    camera_query: Query<&Transform, With<Camera>>,
+    mut selection: ResMut<EditorSelection>,
+    mut level: ResMut<Level>,
+) {
+    let camera = camera_query.single().unwrap();
+    let cursor = match cursor_position(&windows, camera) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    if input.just_pressed(KeyCode::Key1) {
+        level.obstacles.push(Obstacle {
+            shape: ObstacleShape::Cuboid {
+                half_width: 64.,
+                half_height: 32.,
+            },
+            position: cursor.into(),
+            rotation: 0.,
+        });
+        selection.element = Some(Element::Obstacle(level.obstacles.len() - 1));
+    } else if input.just_pressed(KeyCode::Key2) {
+        level.obstacles.push(Obstacle {
+            shape: ObstacleShape::Cuboid {
+                half_width: 128.,
+                half_height: 32.,
+            },
+            position: cursor.into(),
+            rotation: std::f32::consts::FRAC_PI_4,
+        });
+        selection.element = Some(Element::Obstacle(level.obstacles.len() - 1));
+    } else if input.just_pressed(KeyCode::Key3) {
+        let hole = [
+            cursor.x - NEW_HOLE_WIDTH / 2.,
+            cursor.x + NEW_HOLE_WIDTH / 2.,
+        ];
+        selection.element = add_hole(&mut level, hole).map(Element::Hole);
+    } else if input.just_pressed(KeyCode::S) && !is_control_pressed(&input) {
+        let wheel_height = level.start.wheel[1];
+        move_element(
+            &mut level,
+            Element::Start,
+            Vec2::new(cursor.x, wheel_height),
+        );
+        selection.element = Some(Element::Start);
+    } else if input.just_pressed(KeyCode::F) {
+        move_element(&mut level, Element::FinishLine, cursor);
+        selection.element = Some(Element::FinishLine);
+    }
+}
+
+fn move_editor_camera(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    let mut direction = 0.;
+    if input.pressed(KeyCode::A) || input.pressed(KeyCode::Left) {
+        direction -= 1.;
+    }
+    if input.pressed(KeyCode::D) || input.pressed(KeyCode::Right) {
+        direction += 1.;
+    }
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation.x += direction * CAMERA_SPEED * time.delta_seconds();
+    }
+}
+
+fn is_control_pressed(input: &Input<KeyCode>) -> bool {
+    input.pressed(KeyCode::LControl) || input.pressed(KeyCode::RControl)
+}
+
+fn save_and_reload(input: Res<Input<KeyCode>>, mut level: ResMut<Level>) {
+    if !is_control_pressed(&input) {
+        return;
+    }
+    if input.just_pressed(KeyCode::S) {
+        match save_level(&level) {
+            Ok(path) => info!("Saved level to {}", path),
+            Err(error) => warn!("Failed to save level: {}", error),
+        }
+    } else if input.just_pressed(KeyCode::L) {
+        match load_level(&level.id) {
+            Ok(loaded) => *level = loaded,
+            Err(error) => warn!("Failed to reload level: {}", error),
+        }
+    }
+}
+
+/// G generates a level from a random seed. Ctrl+G starts typing in a seed instead, which is
+/// confirmed with Enter and canceled with Escape.
+fn generate(
+    mut input: ResMut<Input<KeyCode>>,
+    mut seed_input: ResMut<SeedInput>,
+    mut selection: ResMut<EditorSelection>,
+    mut level: ResMut<Level>,
+    mut help_text: Query<&mut Text, With<EditorUi>>,
+) {
+    let mut seed = None;
+    if let Some(digits) = seed_input.0.as_mut() {
+        let keys: Vec<KeyCode> = input.get_just_pressed().copied().collect();
+        let mut done = false;
+        for key in keys {
+            // the other editor systems should not act on the typed keys
+            input.reset(key);
+            match key {
+                KeyCode::Return | KeyCode::NumpadEnter => {
+                    seed = digits.parse().ok();
+                    done = true;
+                }
+                KeyCode::Escape => done = true,
+                KeyCode::Back => {
+                    digits.pop();
+                }
+                key => {
+                    if let Some(digit) = digit(key) {
+                        if digits.len() < MAX_SEED_DIGITS {
+                            digits.push(digit);
+                        }
+                    }
+                }
+            }
+        }
+        if done {
+            seed_input.0 = None;
+        }
+    } else if input.just_pressed(KeyCode::G) {
+        if is_control_pressed(&input) {
+            seed_input.0 = Some(String::new());
+        } else {
+            seed = Some(rand::random());
+        }
+    }
+
+    if seed_input.is_changed() {
+        for mut text in help_text.iter_mut() {
+            text.sections[1].value = match &seed_input.0 {
+                Some(digits) => format!("\nSeed: {}_", digits),
+                None => String::new(),
+            };
+        }
+    }
+    if let Some(seed) = seed {
+        info!("Generated level with seed {}", seed);
+        *level = generate_level(seed, GENERATOR_DIFFICULTY);
+        make_custom(&mut level);
+        *selection = EditorSelection::default();
+    }
+}
+
+fn digit(key: KeyCode) -> Option<char> {
+    let digit = match key {
+        KeyCode::Key0 | KeyCode::Numpad0 => '0',
+        KeyCode::Key1 | KeyCode::Numpad1 => '1',
+        KeyCode::Key2 | KeyCode::Numpad2 => '2',
+        KeyCode::Key3 | KeyCode::Numpad3 => '3',
+        KeyCode::Key4 | KeyCode::Numpad4 => '4',
+        KeyCode::Key5 | KeyCode::Numpad5 => '5',
+        KeyCode::Key6 | KeyCode::Numpad6 => '6',
+        KeyCode::Key7 | KeyCode::Numpad7 => '7',
+        KeyCode::Key8 | KeyCode::Numpad8 => '8',
+        KeyCode::Key9 | KeyCode::Numpad9 => '9',
+        _ => return None,
+    };
+    Some(digit)
+}
+
+/// Saved levels live with the other game data, never in the shipped assets
+fn level_file(id: &str) -> String {
+    format!("levels/{}.level.ron", id)
+}
+
+fn save_level(level: &Level) -> Result<String, anyhow::Error> {
+    let file = level_file(&level.id);
+    let serialized = ron::ser::to_string_pretty(level, ron::ser::PrettyConfig::new())?;
+    write_data(&file, &serialized)?;
+
+    Ok(file)
+}
+
+fn load_level(id: &str) -> Result<Level, anyhow::Error> {
+    let content = read_data(&level_file(id))?
+        .ok_or_else(|| anyhow::anyhow!("level {} was never saved", id))?;
+    let level: Level = ron::de::from_str(&content)?;
+    level.validate()?;
+
+    Ok(level)
+}
+
+fn draw_level(
+    mut commands: Commands,
+    level: Res<Level>,
+    selection: Res<EditorSelection>,
+    textures: Res<TextureAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    view_query: Query<Entity, With<EditorView>>,
+) {
+    if !level.is_changed() && !selection.is_changed() {
+        return;
+    }
+    for entity in view_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let selected_color = Color::ORANGE;
+
+    for [start, end] in level.ground_segments() {
+        let (start, end) = (start / PHYSICS_SCALE, end / PHYSICS_SCALE);
+        spawn_collider(
+            &mut commands,
+            ColliderShape::cuboid((end - start) / 2., BOULDER_HEIGTH),
+            Isometry::from(Point2::from([start + (end - start) / 2., 0.])),
+            ColliderDebugRender::default(),
+        );
+    }
+    for (index, collider) in level.colliders().drain(..).enumerate() {
+        spawn_collider(
+            &mut commands,
+            collider.shape,
+            collider.position.0,
+            if selection.element == Some(Element::Obstacle(index)) {
+                selected_color.into()
+            } else {
+                ColliderDebugRender::default()
+            },
+        );
+    }
+    for (index, hole) in level.holes.iter().enumerate() {
+        let color = if selection.element == Some(Element::Hole(index)) {
+            selected_color
+        } else {
+            Color::rgba(0.8, 0.2, 0.2, 0.5)
+        };
+        spawn_marker(
+            &mut commands,
+            &mut materials,
+            color,
+            Vec2::new((hole[0] + hole[1]) / 2., 0.),
+            Vec2::new(hole[1] - hole[0], 2. * BOULDER_HEIGTH * PHYSICS_SCALE),
+        );
+    }
+    let finish_color = if selection.element == Some(Element::FinishLine) {
+        selected_color
+    } else {
+        Color::WHITE
+    };
+    spawn_marker(
+        &mut commands,
+        &mut materials,
+        finish_color,
+        Vec2::new(level.finish_line, 300.),
+        Vec2::new(4., 600.),
+    );
+
+    let start = &level.start;
+    for (position, texture, scale) in [
+        (start.wheel, textures.wheel.clone(), 0.25),
+        (start.body, textures.body.clone(), 0.125),
+        (start.head, textures.head.clone(), 0.125),
+    ] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(texture.into()),
+                transform: Transform {
+                    translation: Vec3::new(position[0], position[1], 1.),
+                    scale: Vec3::splat(scale),
+                    ..Transform::default()
+                },
+                ..Default::default()
+            })
+            .insert(EditorView);
+    }
+    if selection.element == Some(Element::Start) {
+        spawn_marker(
+            &mut commands,
+            &mut materials,
+            Color::rgba(1., 0.65, 0., 0.4),
+            Vec2::new(start.wheel[0], (start.wheel[1] + start.head[1]) / 2.),
+            Vec2::new(64., start.head[1] - start.wheel[1] + 64.),
+        );
+    }
+}
+
+fn spawn_collider(
+    commands: &mut Commands,
+    shape: ColliderShape,
+    isometry: Isometry2<f32>,
+    render: ColliderDebugRender,
+) {
+    commands
+        .spawn_bundle(ColliderBundle {
+            shape,
+            position: ColliderPosition(isometry),
+            flags: ColliderFlags {
+                collision_groups: InteractionGroups::none(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(render)
+        .insert(ColliderPositionSync::Discrete)
+        .insert(EditorView);
+}
+
+fn spawn_marker(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    color: Color,
+    center: Vec2,
+    size: Vec2,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(color.into()),
+            sprite: Sprite::new(size),
+            transform: Transform::from_translation(center.extend(2.)),
+            ..Default::default()
+        })
+        .insert(EditorView);
+}
@@ -0,0 +1,222 @@
+use crate::actions::{Actions, ActionsSystem};
+use crate::camera::CameraSettings;
+use crate::generator::generate_section;
+use crate::levels::{spawn_platform, ForLevel, Level, StartingPoint};
+use crate::loading::FontAssets;
+use crate::physics::{riding, GameStage};
+use crate::player::{spawn_ground_segment, Body, PHYSICS_SCALE};
+use crate::theme::Theme;
+use crate::GameState;
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+
+pub struct EndlessPlugin;
+
+/// End of the flat starting area where the first chunk begins
+const CHUNKS_START: f32 = 400.;
+const CHUNK_LENGTH: f32 = 1600.;
+/// Chunks are kept alive this far in front of the rider
+const VIEW_AHEAD: f32 = 1600.;
+/// Chunks are kept alive this far behind the rider
+const VIEW_BEHIND: f32 = 1200.;
+/// Chunks starting further away than this are generated at full difficulty
+const FULL_DIFFICULTY_DISTANCE: f32 = 32000.;
+
+impl Plugin for EndlessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<EndlessRun>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::PrepareLevel).with_system(reset_run.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::InLevel).with_system(show_distance.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel)
+                    .with_system(stream_chunks.system())
+                    .with_system(track_distance.system()),
+            )
+            .add_system_set_to_stage(
+                GameStage::PhysicsStep,
+                SystemSet::new()
+                    .with_run_criteria(riding.system())
+                    .with_system(reset_distance.system().after(ActionsSystem::Replay)),
+            );
+    }
+}
+
+/// State of the current endless run
+#[derive(Default)]
+pub struct EndlessRun {
+    pub seed: u64,
+    /// Furthest distance in meters the rider made it in this run
+    pub distance: f32,
+    /// Entities of all currently spawned chunks by chunk index
+    chunks: BTreeMap<u32, Vec<Entity>>,
+}
+
+struct DistanceText;
+
+/// The starting area of the endless mode. Everything behind it is streamed in while riding.
+pub fn endless_level() -> Level {
+    Level {
+        id: "endless".to_string(),
+        name: "Endless".to_string(),
+        version: 1,
+        finish_line: f32::INFINITY,
+        ground: [-400., CHUNKS_START],
+        holes: vec![],
+        terrain: vec![],
+        obstacles: vec![],
+        start: StartingPoint::default(),
+        decorations: vec![],
+        endless: true,
+        camera: CameraSettings::default(),
+        theme: Theme::default(),
+        backgrounds: None,
+    }
+}
+
+/// Switch to the endless mode with fresh terrain
+pub fn start_endless_run(level: &mut Level, run: &mut EndlessRun) {
+    *level = endless_level();
+    run.seed = rand::random();
+}
+
+fn reset_run(mut run: ResMut<EndlessRun>) {
+    run.chunks.clear();
+    run.distance = 0.;
+}
+
+fn chunk_index(x: f32) -> u32 {
+    ((x - CHUNKS_START) / CHUNK_LENGTH).max(0.) as u32
+}
+
+fn stream_chunks(
+    mut commands: Commands,
+    level: Res<Level>,
+    mut run: ResMut<EndlessRun>,
+    body_query: Query<&Transform, With<Body>>,
+) {
+    if !level.endless {
+        return;
+    }
+    let rider = match body_query.single() {
+        Ok(transform) => transform.translation.x,
+        Err(_) => return,
+    };
+    let first = chunk_index(rider - VIEW_BEHIND);
+    let last = chunk_index(rider + VIEW_AHEAD);
+    let outdated: Vec<u32> = run
+        .chunks
+        .keys()
+        .filter(|index| **index < first || **index > last)
+        .copied()
+        .collect();
+    for index in outdated {
+        for entity in run.chunks.remove(&index).unwrap() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for index in first..=last {
+        if !run.chunks.contains_key(&index) {
+            let entities = spawn_chunk(&mut commands, level.theme, run.seed, index);
+            run.chunks.insert(index, entities);
+        }
+    }
+}
+
+/// Spawn ground and obstacles of one chunk
+///
+/// Every chunk is generated from its own random stream, so despawned chunks come back the same.
+fn spawn_chunk(commands: &mut Commands, theme: Theme, seed: u64, index: u32) -> Vec<Entity> {
+    let start = CHUNKS_START + index as f32 * CHUNK_LENGTH;
+    let end = start + CHUNK_LENGTH;
+    let mut random = ChaCha8Rng::seed_from_u64(seed);
+    random.set_stream(index as u64);
+    let difficulty = (start / FULL_DIFFICULTY_DISTANCE).min(1.);
+    let section = generate_section(&mut random, start, end, difficulty);
+
+    let mut entities = vec![];
+    let mut segment_start = start;
+    for hole in section.holes.iter() {
+        entities.push(spawn_ground_segment(
+            commands,
+            segment_start,
+            hole[0],
+            theme.ground_color(),
+        ));
+        segment_start = hole[1];
+    }
+    entities.push(spawn_ground_segment(
+        commands,
+        segment_start,
+        end,
+        theme.ground_color(),
+    ));
+    for obstacle in section.obstacles.iter() {
+        entities.push(spawn_platform(
+            commands,
+            obstacle.collider(),
+            theme.platform_color(),
+        ));
+    }
+    entities
+}
+
+fn reset_distance(actions: Res<Actions>, mut run: ResMut<EndlessRun>) {
+    if actions.restart {
+        run.distance = 0.;
+    }
+}
+
+fn track_distance(
+    level: Res<Level>,
+    mut run: ResMut<EndlessRun>,
+    body_query: Query<&Transform, With<Body>>,
+    mut text_query: Query<&mut Text, With<DistanceText>>,
+) {
+    if !level.endless {
+        return;
+    }
+    if let Ok(transform) = body_query.single() {
+        run.distance = run.distance.max(transform.translation.x / PHYSICS_SCALE);
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!("{:.0} m", run.distance);
+    }
+}
+
+fn show_distance(mut commands: Commands, level: Res<Level>, font_assets: Res<FontAssets>) {
+    if !level.endless {
+        return;
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "0 m".to_string(),
+                    style: TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(DistanceText)
+        .insert(ForLevel);
+}
@@ -0,0 +1,212 @@
+use crate::camera::CameraSettings;
+use crate::levels::{Decoration, DecorationTexture, Level, Obstacle, ObstacleShape, StartingPoint};
+use crate::player::{BOULDER_HEIGTH, PHYSICS_SCALE, WHEEL_RADIUS};
+use crate::theme::Theme;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Narrowest generated hole. Anything smaller is just a bump for the wheel
+pub const MIN_HOLE_WIDTH: f32 = 96.;
+/// Widest hole the rider can jump over from flat ground
+pub const MAX_HOLE_WIDTH: f32 = 200.;
+pub const MIN_RAMP_ANGLE: f32 = std::f32::consts::PI / 18.;
+/// Steepest ramp the wheel can still roll up
+pub const MAX_RAMP_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+pub const MIN_BOULDER_RADIUS: f32 = 8.;
+/// Boulders have to stay below the wheel's axle to be rolled over
+pub const MAX_BOULDER_RADIUS: f32 = 0.75 * WHEEL_RADIUS * PHYSICS_SCALE;
+
+/// Flat ground in front of the start and behind every hole
+const SAFE_DISTANCE: f32 = 400.;
+const MIN_FEATURE_SPACING: f32 = 200.;
+const RAMP_HALF_WIDTH: f32 = 128.;
+const RAMP_HALF_HEIGHT: f32 = 32.;
+const GROUND_TOP: f32 = BOULDER_HEIGTH * PHYSICS_SCALE;
+
+/// Holes and obstacles generated for a stretch of ground
+#[derive(Default)]
+pub struct Section {
+    pub holes: Vec<[f32; 2]>,
+    pub obstacles: Vec<Obstacle>,
+}
+
+/// Generate a complete level. The same seed and difficulty always result in the same level.
+///
+/// The difficulty is clamped to `0..=1`.
+pub fn generate_level(seed: u64, difficulty: f32) -> Level {
+    let difficulty = difficulty.clamp(0., 1.);
+    let mut random = ChaCha8Rng::seed_from_u64(seed);
+    let finish_line = 2400. + (difficulty * 2.).round() * 800.;
+    let section = generate_section(
+        &mut random,
+        SAFE_DISTANCE,
+        finish_line - SAFE_DISTANCE,
+        difficulty,
+    );
+
+    Level {
+        id: format!("generated-{}-{}", seed, (difficulty * 100.).round()),
+        name: format!("Seed {}", seed),
+        version: 1,
+        finish_line,
+        ground: [-SAFE_DISTANCE, finish_line + SAFE_DISTANCE],
+        holes: section.holes,
+        obstacles: section.obstacles,
+        terrain: vec![],
+        start: StartingPoint::default(),
+        decorations: vec![Decoration {
+            texture: DecorationTexture::Finish,
+            position: [finish_line, 250.],
+            scale: 0.5,
+        }],
+        endless: false,
+        camera: CameraSettings::default(),
+        theme: Theme::default(),
+        backgrounds: None,
+    }
+}
+
+/// Fill the ground between `start` and `end` with holes, ramps and boulders
+///
+/// Generated features never reach past `end`, so sections can be placed next to each other.
+pub fn generate_section<R: Rng>(random: &mut R, start: f32, end: f32, difficulty: f32) -> Section {
+    let mut section = Section::default();
+    let mut x = start;
+    loop {
+        x += MIN_FEATURE_SPACING + random.gen::<f32>() * 400. * (1. - difficulty);
+        let intensity = random.gen::<f32>() * (0.25 + 0.75 * difficulty);
+        match random.gen_range(0..3) {
+            0 => {
+                let width = lerp(MIN_HOLE_WIDTH, MAX_HOLE_WIDTH, intensity);
+                if x + width + SAFE_DISTANCE > end {
+                    break;
+                }
+                section.holes.push([x, x + width]);
+                x += width + SAFE_DISTANCE;
+            }
+            1 => {
+                let angle = lerp(MIN_RAMP_ANGLE, MAX_RAMP_ANGLE, intensity);
+                let footprint = 2. * RAMP_HALF_WIDTH * angle.cos();
+                if x + footprint > end {
+                    break;
+                }
+                section.obstacles.push(ramp(x, angle));
+                x += footprint;
+            }
+            _ => {
+                let radius = lerp(MIN_BOULDER_RADIUS, MAX_BOULDER_RADIUS, intensity);
+                if x + 2. * radius > end {
+                    break;
+                }
+                section.obstacles.push(Obstacle {
+                    shape: ObstacleShape::Ball { radius },
+                    position: [x + radius, GROUND_TOP],
+                    rotation: 0.,
+                });
+                x += 2. * radius;
+            }
+        }
+    }
+
+    section
+}
+
+/// A ramp going up to the right with the lower end of its surface at `x` on the ground
+fn ramp(x: f32, angle: f32) -> Obstacle {
+    let (sin, cos) = angle.sin_cos();
+    Obstacle {
+        shape: ObstacleShape::Cuboid {
+            half_width: RAMP_HALF_WIDTH,
+            half_height: RAMP_HALF_HEIGHT,
+        },
+        position: [
+            x + RAMP_HALF_WIDTH * cos + RAMP_HALF_HEIGHT * sin,
+            GROUND_TOP + RAMP_HALF_WIDTH * sin - RAMP_HALF_HEIGHT * cos,
+        ],
+        rotation: angle,
+    }
+}
+
+fn lerp(from: f32, to: f32, amount: f32) -> f32 {
+    from + (to - from) * amount.clamp(0., 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u64; 5] = [0, 1, 42, 1337, u64::MAX];
+    const DIFFICULTIES: [f32; 4] = [0., 0.3, 0.7, 1.];
+    /// Rounding in the angle and width calculations
+    const EPSILON: f32 = 1e-4;
+
+    fn serialized(level: &Level) -> String {
+        ron::to_string(level).unwrap()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_level() {
+        for seed in SEEDS {
+            for difficulty in DIFFICULTIES {
+                assert_eq!(
+                    serialized(&generate_level(seed, difficulty)),
+                    serialized(&generate_level(seed, difficulty)),
+                    "seed {} with difficulty {}",
+                    seed,
+                    difficulty
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_generate_different_levels() {
+        assert_ne!(
+            serialized(&generate_level(1, 0.5)),
+            serialized(&generate_level(2, 0.5))
+        );
+    }
+
+    #[test]
+    fn features_stay_within_limits() {
+        for seed in SEEDS {
+            for difficulty in DIFFICULTIES {
+                let level = generate_level(seed, difficulty);
+                let mut last_end = level.ground[0];
+                for [start, end] in level.holes.iter().copied() {
+                    let width = end - start;
+                    assert!(
+                        (MIN_HOLE_WIDTH - EPSILON..=MAX_HOLE_WIDTH + EPSILON).contains(&width),
+                        "hole of width {} for seed {} with difficulty {}",
+                        width,
+                        seed,
+                        difficulty
+                    );
+                    assert!(start >= last_end, "holes overlap for seed {}", seed);
+                    last_end = end;
+                }
+                assert!(last_end <= level.ground[1]);
+                for obstacle in level.obstacles.iter() {
+                    match obstacle.shape {
+                        ObstacleShape::Cuboid { .. } => assert!(
+                            (MIN_RAMP_ANGLE - EPSILON..=MAX_RAMP_ANGLE + EPSILON)
+                                .contains(&obstacle.rotation),
+                            "ramp at {} for seed {} with difficulty {}",
+                            obstacle.rotation,
+                            seed,
+                            difficulty
+                        ),
+                        ObstacleShape::Ball { radius } => assert!(
+                            (MIN_BOULDER_RADIUS - EPSILON..=MAX_BOULDER_RADIUS + EPSILON)
+                                .contains(&radius),
+                            "boulder of radius {} for seed {} with difficulty {}",
+                            radius,
+                            seed,
+                            difficulty
+                        ),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::levels::{ForLevel, Level};
+use crate::loading::TextureAssets;
+use crate::physics::{riding, GameStage};
+use crate::player::{Body, Head, Wheel, PHYSICS_SCALE};
+use crate::progress::record_key;
+use crate::save::{read_data, write_data};
+use crate::speedrun::{LevelTimer, NewRecord};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GhostRecording>()
+            .init_resource::<BestGhost>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::PrepareLevel)
+                    .with_system(clear_recording.system())
+                    .with_system(load_best_ghost.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::InLevel).with_system(spawn_ghost.system()),
+            )
+            .add_system_set_to_stage(
+                GameStage::PhysicsStep,
+                SystemSet::new()
+                    .with_run_criteria(riding.system())
+                    .after(PhysicsSystems::StepWorld)
+                    .with_system(record.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel).with_system(play_ghost.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Finished).with_system(save_ghost.system()),
+            );
+    }
+}
+
+/// Pose of one part of the rider: x, y and rotation angle
+type Pose = [f32; 3];
+
+/// Poses of the rider during a run
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct GhostRecording {
+    /// Seconds of one physics step
+    step: f32,
+    /// Pose after every physics step since the level timer started
+    frames: Vec<GhostFrame>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct GhostFrame {
+    wheel: Pose,
+    body: Pose,
+    head: Pose,
+}
+
+/// Recording of the personal best for the current level, if there is one
+#[derive(Default)]
+struct BestGhost(Option<GhostRecording>);
+
+#[derive(Clone, Copy)]
+enum GhostPart {
+    Wheel,
+    Body,
+    Head,
+}
+
+fn ghost_file(level: &Level) -> String {
+    format!("ghosts/{}.ron", record_key(&level.id, level.version))
+}
+
+/// Pose in pixels, like the sprites are placed
+fn pose(position: &RigidBodyPosition) -> Pose {
+    let isometry = position.position;
+    [
+        isometry.translation.x * PHYSICS_SCALE,
+        isometry.translation.y * PHYSICS_SCALE,
+        isometry.rotation.angle(),
+    ]
+}
+
+fn clear_recording(mut recording: ResMut<GhostRecording>) {
+    recording.frames.clear();
+}
+
+fn load_best_ghost(level: Res<Level>, mut best: ResMut<BestGhost>) {
+    best.0 = None;
+    if level.endless {
+        return;
+    }
+    let parsed = read_data(&ghost_file(&level)).and_then(|content| match content {
+        Some(content) => Ok(Some(ron::de::from_str::<GhostRecording>(&content)?)),
+        None => Ok(None),
+    });
+    match parsed {
+        Ok(ghost) => best.0 = ghost,
+        Err(error) => warn!("Failed to load ghost of level {}: {}", level.id, error),
+    }
+}
+
+/// Keep the rider's pose after every physics step of a running timer
+fn record(
+    timer: Res<LevelTimer>,
+    integration_parameters: Res<IntegrationParameters>,
+    mut recording: ResMut<GhostRecording>,
+    wheel_query: Query<&RigidBodyPosition, With<Wheel>>,
+    body_query: Query<&RigidBodyPosition, With<Body>>,
+    head_query: Query<&RigidBodyPosition, With<Head>>,
+) {
+    // the timer stops when restarting
+    if !timer.is_running() {
+        recording.frames.clear();
+        return;
+    }
+    recording.step = integration_parameters.dt;
+    if let (Ok(wheel), Ok(body), Ok(head)) = (
+        wheel_query.single(),
+        body_query.single(),
+        head_query.single(),
+    ) {
+        recording.frames.push(GhostFrame {
+            wheel: pose(wheel),
+            body: pose(body),
+            head: pose(head),
+        });
+    }
+}
+
+fn save_ghost(
+    mut new_records: EventReader<NewRecord>,
+    level: Res<Level>,
+    recording: Res<GhostRecording>,
+    mut best: ResMut<BestGhost>,
+) {
+    let new_record = new_records.iter().next().is_some();
+    if !new_record || recording.frames.is_empty() || level.is_custom() {
+        return;
+    }
+    best.0 = Some(recording.clone());
+    let result = ron::ser::to_string(&*recording)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| write_data(&ghost_file(&level), &content));
+    if let Err(error) = result {
+        warn!("Failed to save ghost of level {}: {}", level.id, error);
+    }
+}
+
+/// Spawn see-through copies of the rider's sprites without any physics components
+fn spawn_ghost(
+    mut commands: Commands,
+    best: Res<BestGhost>,
+    textures: Res<TextureAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    wheel_query: Query<&Transform, With<Wheel>>,
+    body_query: Query<&Transform, With<Body>>,
+    head_query: Query<&Transform, With<Head>>,
+) {
+    if best.0.is_none() {
+        return;
+    }
+    let parts = [
+        (GhostPart::Wheel, &textures.wheel, wheel_query.single()),
+        (GhostPart::Body, &textures.body, body_query.single()),
+        (GhostPart::Head, &textures.head, head_query.single()),
+    ];
+    for (part, texture, rider_transform) in parts.iter() {
+        let scale = rider_transform
+            .as_ref()
+            .map(|transform| transform.scale)
+            .unwrap_or(Vec3::ONE);
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: materials.add(ColorMaterial {
+                    color: Color::rgba(1., 1., 1., 0.4),
+                    texture: Some((*texture).clone()),
+                }),
+                transform: Transform::from_scale(scale),
+                ..Default::default()
+            })
+            .insert(*part)
+            .insert(ForLevel);
+    }
+}
+
+/// Move the ghost to its recorded pose at the current time of the level timer
+fn play_ghost(
+    best: Res<BestGhost>,
+    timer: Res<LevelTimer>,
+    mut ghost_query: Query<(&GhostPart, &mut Transform)>,
+) {
+    let ghost = match &best.0 {
+        Some(ghost) if !ghost.frames.is_empty() && ghost.step > 0. => ghost,
+        _ => return,
+    };
+    let frames = &ghost.frames;
+    // the first frame is recorded once the timer counted one step
+    let step = (timer.seconds() / ghost.step - 1.).max(0.);
+    let index = step.floor() as usize;
+    let frame = match frames.get(index + 1) {
+        Some(next) => interpolate(&frames[index], next, step.fract()),
+        None => frames[frames.len() - 1].clone(),
+    };
+    for (part, mut transform) in ghost_query.iter_mut() {
+        let [x, y, angle] = match part {
+            GhostPart::Wheel => frame.wheel,
+            GhostPart::Body => frame.body,
+            GhostPart::Head => frame.head,
+        };
+        transform.translation.x = x;
+        transform.translation.y = y;
+        transform.rotation = Quat::from_rotation_z(angle);
+    }
+}
+
+fn interpolate(from: &GhostFrame, to: &GhostFrame, amount: f32) -> GhostFrame {
+    let lerp = |a: Pose, b: Pose| {
+        let mut angle = b[2] - a[2];
+        // take the short way around
+        if angle > std::f32::consts::PI {
+            angle -= std::f32::consts::TAU;
+        } else if angle < -std::f32::consts::PI {
+            angle += std::f32::consts::TAU;
+        }
+        [
+            a[0] + (b[0] - a[0]) * amount,
+            a[1] + (b[1] - a[1]) * amount,
+            a[2] + angle * amount,
+        ]
+    };
+    GhostFrame {
+        wheel: lerp(from.wheel, to.wheel),
+        body: lerp(from.body, to.body),
+        head: lerp(from.head, to.head),
+    }
+}
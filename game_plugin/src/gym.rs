@@ -0,0 +1,320 @@
+use crate::actions::{Actions, InputSource};
+use crate::endless::{endless_level, EndlessRun};
+use crate::levels::{level_order, Level};
+use crate::loading::LevelAssets;
+use crate::player::{Body, Head, Wheel};
+use crate::{GameState, HeadlessGamePlugin};
+use anyhow::bail;
+use bevy::app::App;
+use bevy::asset::AssetServerSettings;
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{
+    QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet,
+};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// Upper bound of frames to wait for loading or starting a level
+const MAX_WAITING_FRAMES: usize = 60 * 60;
+/// The terrain is sampled from this many meters behind the wheel...
+const TERRAIN_BEHIND: f32 = 2.;
+/// ...to this many meters ahead of it
+const TERRAIN_AHEAD: f32 = 12.;
+/// Meters between two terrain samples
+const TERRAIN_STEP: f32 = 0.5;
+/// Height in meters above the wheel from which the terrain is sampled
+const TERRAIN_RAY_HEIGHT: f32 = 20.;
+/// Reward for crossing the finish line
+const FINISH_REWARD: f32 = 10.;
+/// Penalty for falling down or into a hole
+const LOST_PENALTY: f32 = 10.;
+
+/// The game as an environment for reinforcement learning
+///
+/// Every [`Gym::step`] simulates one step of the fixed physics timestep as fast as possible.
+/// The reward is the progress of the rider's body in meters, plus a bonus for finishing or a
+/// penalty for losing the level.
+pub struct Gym {
+    app: App,
+    /// Horizontal position of the body after the last step
+    progress: f32,
+}
+
+/// The action of one frame. Both axes range from -1 to 1.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct GymAction {
+    #[serde(default)]
+    pub paddling: f32,
+    #[serde(default)]
+    pub head_balance: f32,
+    #[serde(default)]
+    pub jump: bool,
+}
+
+/// Everything the agent knows about the level. All values are in physics units.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Observation {
+    pub wheel: PartState,
+    pub body: PartState,
+    pub head: PartState,
+    /// Height of the ground relative to the wheel's center, sampled every half meter from two
+    /// meters behind to twelve meters ahead of the wheel. `None` above holes.
+    pub terrain: Vec<Option<f32>>,
+}
+
+/// Rigid-body state of one part of the rider
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PartState {
+    pub position: [f32; 2],
+    /// Rotation in radians, counterclockwise
+    pub angle: f32,
+    pub linvel: [f32; 2],
+    pub angvel: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    /// The rider lost, fell into a hole or finished the level
+    pub done: bool,
+}
+
+/// One line of the JSON protocol spoken by [`serve`]
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Reset {
+        level: String,
+        #[serde(default)]
+        seed: u64,
+    },
+    Step(GymAction),
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl Gym {
+    /// A headless game loading its levels from the given assets folder
+    pub fn new(asset_folder: &str) -> anyhow::Result<Gym> {
+        let mut builder = App::build();
+        builder
+            .insert_resource(AssetServerSettings {
+                asset_folder: asset_folder.to_string(),
+            })
+            .add_plugins(MinimalPlugins)
+            .add_plugin(HeadlessGamePlugin)
+            .insert_resource(InputSource::External)
+            .init_resource::<Observation>()
+            .add_system_to_stage(CoreStage::PostUpdate, observe.system());
+        let mut gym = Gym {
+            app: builder.app,
+            progress: 0.,
+        };
+        for _ in 0..MAX_WAITING_FRAMES {
+            match gym.state() {
+                GameState::Menu => return Ok(gym),
+                GameState::LoadingFailed => {
+                    bail!("Failed to load the levels from {}", asset_folder)
+                }
+                _ => {
+                    gym.app.update();
+                    // levels are loaded on another thread
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+            }
+        }
+        bail!("Loading the levels from {} timed out", asset_folder)
+    }
+
+    /// Start the level with the given id. The seed only matters for the endless mode.
+    pub fn reset(&mut self, level_id: &str, seed: u64) -> anyhow::Result<Observation> {
+        if self.state() != GameState::Menu {
+            self.game_state().replace(GameState::Menu)?;
+            self.app.update();
+        }
+        let level = if level_id == endless_level().id {
+            self.app
+                .world
+                .get_resource_mut::<EndlessRun>()
+                .unwrap()
+                .seed = seed;
+            endless_level()
+        } else {
+            let level_assets = self.app.world.get_resource::<LevelAssets>().unwrap();
+            let levels = self.app.world.get_resource::<Assets<Level>>().unwrap();
+            match level_order(level_assets, levels)
+                .into_iter()
+                .find(|level| level.id == level_id)
+            {
+                Some(level) => level.clone(),
+                None => bail!("There is no level {}", level_id),
+            }
+        };
+        self.app.world.insert_resource(level);
+        self.app.world.insert_resource(Actions::default());
+        self.game_state().set(GameState::Prepare)?;
+        for _ in 0..MAX_WAITING_FRAMES {
+            self.app.update();
+            if self.state() == GameState::InLevel {
+                // the level's colliders only become visible to the terrain raycasts with the
+                // first physics step after they were spawned
+                self.app.update();
+                let observation = self.observation();
+                self.progress = observation.body.position[0];
+                return Ok(observation);
+            }
+        }
+        bail!("The level {} did not start", level_id)
+    }
+
+    /// Apply the action for one frame and advance the physics by one step
+    pub fn step(&mut self, action: &GymAction) -> anyhow::Result<Step> {
+        if self.state() != GameState::InLevel {
+            bail!("No level is running, call reset first");
+        }
+        *self.app.world.get_resource_mut::<Actions>().unwrap() = Actions {
+            paddling: Some(action.paddling.clamp(-1., 1.)),
+            head_balance: Some(action.head_balance.clamp(-1., 1.)),
+            jump: action.jump,
+            restart: false,
+        };
+        self.app.update();
+        let observation = self.observation();
+        let mut reward = observation.body.position[0] - self.progress;
+        self.progress = observation.body.position[0];
+        let state = self.state();
+        match state {
+            GameState::Finished => reward += FINISH_REWARD,
+            GameState::Lost => reward -= LOST_PENALTY,
+            _ => (),
+        }
+        Ok(Step {
+            observation,
+            reward,
+            done: state != GameState::InLevel,
+        })
+    }
+
+    fn observation(&self) -> Observation {
+        self.app
+            .world
+            .get_resource::<Observation>()
+            .unwrap()
+            .clone()
+    }
+
+    fn state(&self) -> GameState {
+        self.app
+            .world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+            .clone()
+    }
+
+    fn game_state(&mut self) -> Mut<'_, State<GameState>> {
+        self.app
+            .world
+            .get_resource_mut::<State<GameState>>()
+            .unwrap()
+    }
+}
+
+/// Answer JSON requests, one per line, until the input ends
+///
+/// `{"reset":{"level":"tutorial","seed":0}}` answers with an [`Observation`] and
+/// `{"step":{"paddling":0.5,"head_balance":0,"jump":false}}` with a [`Step`]. Failed requests
+/// are answered with `{"error":"..."}`.
+pub fn serve(gym: &mut Gym, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Reset { level, seed }) => gym
+                .reset(&level, seed)
+                .and_then(|observation| Ok(serde_json::to_string(&observation)?)),
+            Ok(Request::Step(action)) => gym
+                .step(&action)
+                .and_then(|step| Ok(serde_json::to_string(&step)?)),
+            Err(error) => Err(error.into()),
+        };
+        let response = response.unwrap_or_else(|error| {
+            serde_json::to_string(&ErrorResponse {
+                error: error.to_string(),
+            })
+            .unwrap()
+        });
+        writeln!(output, "{}", response)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn part_state(position: &RigidBodyPosition, velocity: &RigidBodyVelocity) -> PartState {
+    let translation = position.position.translation;
+    PartState {
+        position: [translation.x, translation.y],
+        angle: position.position.rotation.angle(),
+        linvel: [velocity.linvel.x, velocity.linvel.y],
+        angvel: velocity.angvel,
+    }
+}
+
+/// Collect the state of the rider and raycast the terrain around it
+fn observe(
+    mut observation: ResMut<Observation>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    wheel_query: Query<(Entity, &RigidBodyPosition, &RigidBodyVelocity), With<Wheel>>,
+    body_query: Query<(Entity, &RigidBodyPosition, &RigidBodyVelocity), With<Body>>,
+    head_query: Query<(Entity, &RigidBodyPosition, &RigidBodyVelocity), With<Head>>,
+) {
+    let (
+        (wheel, wheel_position, wheel_velocity),
+        (body, body_position, body_velocity),
+        (head, head_position, head_velocity),
+    ) = match (
+        wheel_query.single(),
+        body_query.single(),
+        head_query.single(),
+    ) {
+        (Ok(wheel), Ok(body), Ok(head)) => (wheel, body, head),
+        _ => return,
+    };
+    let colliders = QueryPipelineColliderComponentsSet(&collider_query);
+    let not_the_rider = |handle: ColliderHandle| {
+        let entity = handle.entity();
+        entity != wheel && entity != body && entity != head
+    };
+    let center = wheel_position.position.translation;
+    let samples = ((TERRAIN_BEHIND + TERRAIN_AHEAD) / TERRAIN_STEP) as usize + 1;
+    observation.terrain = (0..samples)
+        .map(|sample| {
+            let x = center.x - TERRAIN_BEHIND + sample as f32 * TERRAIN_STEP;
+            let ray = Ray::new(
+                [x, center.y + TERRAIN_RAY_HEIGHT].into(),
+                Vector::new(0., -1.),
+            );
+            query_pipeline
+                .cast_ray(
+                    &colliders,
+                    &ray,
+                    2. * TERRAIN_RAY_HEIGHT,
+                    true,
+                    InteractionGroups::all(),
+                    Some(&not_the_rider),
+                )
+                .map(|(_, toi)| TERRAIN_RAY_HEIGHT - toi)
+        })
+        .collect();
+    observation.wheel = part_state(wheel_position, wheel_velocity);
+    observation.body = part_state(body_position, body_velocity);
+    observation.head = part_state(head_position, head_velocity);
+}
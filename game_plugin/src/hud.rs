@@ -0,0 +1,193 @@
+use crate::levels::Level;
+use crate::loading::FontAssets;
+use crate::lost::ButtonMaterials;
+use crate::player::{Body, Wheel, PHYSICS_SCALE};
+use crate::settings::Settings;
+use crate::speedrun::LevelTimer;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub struct HudPlugin;
+
+/// Lean angle at the ends of the gauge
+const MAX_LEAN: f32 = std::f32::consts::FRAC_PI_2;
+/// From this lean angle on the gauge warns about falling over
+const DANGEROUS_LEAN: f32 = std::f32::consts::FRAC_PI_4;
+const GAUGE_WIDTH: f32 = 200.;
+const MARKER_WIDTH: f32 = 8.;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<HudMaterials>()
+            .add_system_set(SystemSet::on_enter(GameState::InLevel).with_system(spawn_hud.system()))
+            .add_system_set(
+                SystemSet::on_resume(GameState::InLevel).with_system(spawn_hud.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel).with_system(update_hud.system()),
+            )
+            .add_system_set(
+                SystemSet::on_pause(GameState::InLevel).with_system(despawn_hud.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::InLevel).with_system(despawn_hud.system()),
+            );
+    }
+}
+
+struct HudMaterials {
+    gauge: Handle<ColorMaterial>,
+    marker: Handle<ColorMaterial>,
+    danger: Handle<ColorMaterial>,
+}
+
+impl FromWorld for HudMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        HudMaterials {
+            gauge: materials.add(Color::rgba(0.15, 0.15, 0.15, 0.6).into()),
+            marker: materials.add(Color::rgb(0.9, 0.9, 0.9).into()),
+            danger: materials.add(Color::rgb(0.9, 0.2, 0.2).into()),
+        }
+    }
+}
+
+struct Hud;
+
+#[derive(Clone, Copy)]
+enum HudText {
+    Time,
+    Distance,
+    Speed,
+}
+
+struct LeanMarker;
+
+fn spawn_hud(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    level: Res<Level>,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    hud_materials: Res<HudMaterials>,
+) {
+    if !settings.show_hud {
+        return;
+    }
+    let mut texts = vec![HudText::Time, HudText::Speed];
+    if !level.endless {
+        texts.insert(1, HudText::Distance);
+    }
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.none.clone(),
+            ..Default::default()
+        })
+        .insert(Hud)
+        .with_children(|parent| {
+            for text in texts {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "",
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 25.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(text);
+            }
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(GAUGE_WIDTH), Val::Px(16.)),
+                        margin: Rect::all(Val::Px(5.)),
+                        ..Default::default()
+                    },
+                    material: hud_materials.gauge.clone(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: Rect {
+                                    left: Val::Px((GAUGE_WIDTH - MARKER_WIDTH) / 2.),
+                                    ..Default::default()
+                                },
+                                size: Size::new(Val::Px(MARKER_WIDTH), Val::Percent(100.)),
+                                ..Default::default()
+                            },
+                            material: hud_materials.marker.clone(),
+                            ..Default::default()
+                        })
+                        .insert(LeanMarker);
+                });
+        });
+}
+
+fn update_hud(
+    level: Res<Level>,
+    timer: Res<LevelTimer>,
+    hud_materials: Res<HudMaterials>,
+    body_query: Query<&Transform, With<Body>>,
+    wheel_query: Query<&RigidBodyVelocity, With<Wheel>>,
+    mut text_query: Query<(&HudText, &mut Text)>,
+    mut marker_query: Query<(&mut Style, &mut Handle<ColorMaterial>), With<LeanMarker>>,
+) {
+    let body = match body_query.single() {
+        Ok(body) => body,
+        Err(_) => return,
+    };
+    let speed = wheel_query
+        .single()
+        .map(|velocity| velocity.linvel.x)
+        .unwrap_or(0.);
+    let remaining = ((level.finish_line - body.translation.x) / PHYSICS_SCALE).max(0.);
+    for (hud_text, mut text) in text_query.iter_mut() {
+        text.sections[0].value = match hud_text {
+            HudText::Time => format!("{:.2} s", timer.seconds()),
+            HudText::Distance => format!("{:.0} m to the finish", remaining),
+            HudText::Speed => format!("{:.1} m/s", speed),
+        };
+    }
+
+    // angle between the body's up direction and the vertical, positive when leaning forward
+    let up = body.rotation * Vec3::Y;
+    let lean = up.x.atan2(up.y);
+    let offset = (lean / MAX_LEAN).clamp(-1., 1.) * (GAUGE_WIDTH - MARKER_WIDTH) / 2.;
+    for (mut style, mut material) in marker_query.iter_mut() {
+        style.position.left = Val::Px((GAUGE_WIDTH - MARKER_WIDTH) / 2. + offset);
+        let target = if lean.abs() > DANGEROUS_LEAN {
+            &hud_materials.danger
+        } else {
+            &hud_materials.marker
+        };
+        if *material != *target {
+            *material = target.clone();
+        }
+    }
+}
+
+fn despawn_hud(mut commands: Commands, hud_query: Query<Entity, With<Hud>>) {
+    for entity in hud_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,92 @@
+use crate::endless::{start_endless_run, EndlessRun};
+use crate::levels::{level_order, Level};
+use crate::loading::{FontAssets, LevelAssets};
+use crate::lost::ButtonMaterials;
+use crate::menu::{spawn_menu, ButtonActivated};
+use crate::progress::Progress;
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::LevelSelect).with_system(show_level_select.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::LevelSelect).with_system(select_level.system()),
+        );
+    }
+}
+
+#[derive(Clone)]
+enum LevelSelectButton {
+    Level(Handle<Level>),
+    Locked,
+    Endless,
+    Back,
+}
+
+fn show_level_select(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    progress: Res<Progress>,
+) {
+    let order = level_order(&level_assets, &levels);
+    let mut buttons: Vec<(String, LevelSelectButton)> = level_assets
+        .all()
+        .into_iter()
+        .zip(order.iter())
+        .enumerate()
+        .map(|(index, (handle, level))| {
+            if !progress.is_unlocked(index, &order) {
+                return (
+                    format!("{} - locked", level.name),
+                    LevelSelectButton::Locked,
+                );
+            }
+            let label = match progress.record(level) {
+                Some(record) => format!("{} - {:.2} s", level.name, record.time),
+                None => level.name.clone(),
+            };
+            (label, LevelSelectButton::Level(handle))
+        })
+        .collect();
+    buttons.push(("Endless".to_string(), LevelSelectButton::Endless));
+    buttons.push(("Back".to_string(), LevelSelectButton::Back));
+    spawn_menu(
+        &mut commands,
+        &font_assets,
+        &button_materials,
+        "Level Select",
+        buttons,
+    );
+}
+
+fn select_level(
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<&LevelSelectButton>,
+    levels: Res<Assets<Level>>,
+    mut level: ResMut<Level>,
+    mut run: ResMut<EndlessRun>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for ButtonActivated(entity) in activated.iter() {
+        match buttons.get(*entity) {
+            Ok(LevelSelectButton::Level(handle)) => {
+                *level = levels.get(handle).expect("Level not loaded").clone();
+                state.set(GameState::Prepare).unwrap();
+            }
+            Ok(LevelSelectButton::Endless) => {
+                start_endless_run(&mut level, &mut run);
+                state.set(GameState::Prepare).unwrap();
+            }
+            Ok(LevelSelectButton::Back) => state.set(GameState::Menu).unwrap(),
+            Ok(LevelSelectButton::Locked) | Err(_) => (),
+        }
+    }
+}
@@ -1,163 +1,369 @@
-use crate::actions::Actions;
+use crate::actions::{Actions, ActionsSystem, GameControl, KeyBindings};
 use crate::audio::PlaySoundEffect;
-use crate::loading::FontAssets;
+use crate::background::BackgroundLayer;
+use crate::camera::{CameraSettings, Impact};
+use crate::loading::{FontAssets, LevelAssets, TextureAssets};
 use crate::lost::{ButtonInteraction, ButtonMaterials};
 use crate::nalgebra::Isometry2;
+use crate::physics::{riding, GameStage};
 use crate::player::*;
+use crate::scenery::Scenery;
+use crate::theme::Theme;
 use crate::GameState;
 use bevy::prelude::*;
-use bevy_rapier2d::na::Point2;
+use bevy::reflect::TypeUuid;
+use bevy_rapier2d::na::{DVector, Point2};
+use bevy_rapier2d::physics::PhysicsSystems;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub struct LevelsPlugin;
 
-#[derive(PartialEq)]
-pub enum Level {
-    Tutorial,
-    First,
-    Second,
-    Third,
+/// A level as described by a `.level.ron` file in `assets/levels`
+///
+/// All positions and sizes are given in pixels. The currently played level is kept as a resource.
+#[derive(Clone, Debug, Deserialize, Serialize, TypeUuid)]
+#[uuid = "1d9e5a4c-7f3b-4c55-8b8e-2f4c6d0a9e13"]
+pub struct Level {
+    pub id: String,
+    pub name: String,
+    pub version: u32,
+    pub finish_line: f32,
+    /// Left and right end of the ground. There is a wall at both ends.
+    pub ground: [f32; 2],
+    /// Gaps in the ground given as start and end, ordered from left to right
+    pub holes: Vec<[f32; 2]>,
+    pub obstacles: Vec<Obstacle>,
+    /// Ground at other heights than the flat strips, e.g. hills or filled up holes
+    #[serde(default)]
+    pub terrain: Vec<Terrain>,
+    pub start: StartingPoint,
+    pub decorations: Vec<Decoration>,
+    /// Terrain is streamed in by the endless mode instead of ending at the finish line
+    #[serde(default)]
+    pub endless: bool,
+    #[serde(default)]
+    pub camera: CameraSettings,
+    #[serde(default)]
+    pub theme: Theme,
+    /// Replaces the background layers of the theme
+    #[serde(default)]
+    pub backgrounds: Option<Vec<BackgroundLayer>>,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StartingPoint {
-    wheel: Point<f32>,
-    body: Point<f32>,
-    head: Point<f32>,
+    pub wheel: [f32; 2],
+    pub body: [f32; 2],
+    pub head: [f32; 2],
+}
+
+impl Default for StartingPoint {
+    /// The rider standing upright on the ground at x = 0
+    fn default() -> Self {
+        let wheel = 0.5 * BOULDER_HEIGTH + WHEEL_RADIUS;
+        let body = 0.5 * BOULDER_HEIGTH + 2. * WHEEL_RADIUS + 0.5 * BODY_LENGTH + BODY_RADIUS;
+        let head =
+            0.5 * BOULDER_HEIGTH + 2. * WHEEL_RADIUS + BODY_LENGTH + 2. * BODY_RADIUS + HEAD_RADIUS;
+        StartingPoint {
+            wheel: [0., wheel * PHYSICS_SCALE],
+            body: [0., body * PHYSICS_SCALE],
+            head: [0., head * PHYSICS_SCALE],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Obstacle {
+    pub shape: ObstacleShape,
+    pub position: [f32; 2],
+    /// Rotation in radians
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ObstacleShape {
+    Cuboid { half_width: f32, half_height: f32 },
+    Ball { radius: f32 },
+}
+
+/// Ground following a line from left to right
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Terrain {
+    /// Corners of the ground's surface
+    Polyline(Vec<[f32; 2]>),
+    /// Heights of the surface, evenly spaced from `start` to `end`
+    Heightfield {
+        start: f32,
+        end: f32,
+        heights: Vec<f32>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Decoration {
+    pub texture: DecorationTexture,
+    pub position: [f32; 2],
+    pub scale: f32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum DecorationTexture {
+    Finish,
+    PlayerWon,
+    Thanks,
+    Tutorial,
+    TutorialJump,
+    TutorialFalling,
+    TutorialRestart,
+}
+
+impl DecorationTexture {
+    pub fn texture(&self, textures: &TextureAssets) -> Handle<Texture> {
+        match self {
+            DecorationTexture::Finish => textures.finish.clone(),
+            DecorationTexture::PlayerWon => textures.player_won.clone(),
+            DecorationTexture::Thanks => textures.thanks.clone(),
+            DecorationTexture::Tutorial => textures.tutorial.clone(),
+            DecorationTexture::TutorialJump => textures.tutorial_jump.clone(),
+            DecorationTexture::TutorialFalling => textures.tutorial_falling.clone(),
+            DecorationTexture::TutorialRestart => textures.tutorial_restart.clone(),
+        }
+    }
 }
 
 pub struct ForLevel;
 
+/// Levels changed in the editor get ids starting with this, so they are never mistaken for the
+/// level they were made from
+pub const CUSTOM_LEVEL_PREFIX: &str = "custom-";
+
 impl Level {
-    pub fn last() -> Self {
-        Level::Third
+    /// Start and end of all ground segments in between the holes
+    ///
+    /// Only meaningful for levels passing [`Level::validate`].
+    pub fn ground_segments(&self) -> Vec<[f32; 2]> {
+        let mut segments = vec![];
+        let mut start = self.ground[0];
+        for hole in self.holes.iter() {
+            segments.push([start, hole[0]]);
+            start = hole[1];
+        }
+        segments.push([start, self.ground[1]]);
+
+        segments
     }
 
-    pub fn get_starting_points(&self) -> StartingPoint {
-        match self {
-            _ => StartingPoint {
-                wheel: [0., 0.5 * BOULDER_HEIGTH + WHEEL_RADIUS].into(),
-                body: [
-                    0.,
-                    0.5 * BOULDER_HEIGTH + 2. * WHEEL_RADIUS + 0.5 * BODY_LENGTH + BODY_RADIUS,
-                ]
-                .into(),
-                head: [
-                    0.,
-                    0.5 * BOULDER_HEIGTH
-                        + 2. * WHEEL_RADIUS
-                        + BODY_LENGTH
-                        + 2. * BODY_RADIUS
-                        + HEAD_RADIUS,
-                ]
-                .into(),
-            },
-        }
+    /// Made or changed in the editor. Riding it does not count towards progress or records.
+    pub fn is_custom(&self) -> bool {
+        self.id.starts_with(CUSTOM_LEVEL_PREFIX)
     }
 
-    pub fn finish_line(&self) -> f32 {
-        match self {
-            Level::Tutorial => 800. * 3.,
-            Level::First => 800. * 3.,
-            Level::Second => 800. * 3.,
-            Level::Third => 800. * 3.,
+    /// The holes have to be sorted, must not overlap and must lie within the ground
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for [start, end] in self.ground_segments() {
+            if start > end {
+                anyhow::bail!(
+                    "ground segment from {} to {} of level {} is reversed",
+                    start,
+                    end,
+                    self.id
+                );
+            }
         }
+        Ok(())
     }
 
-    pub fn holes(&self) -> Vec<[f32; 2]> {
-        match self {
-            Level::Tutorial => vec![[1600., 1800.]],
-            Level::First => vec![[864., 1000.]],
-            Level::Second => vec![[800., 1250.]],
-            Level::Third => vec![[250., 450.], [800., 1250.]],
+    pub fn colliders(&self) -> Vec<ColliderBundle> {
+        self.obstacles.iter().map(Obstacle::collider).collect()
+    }
+
+    /// Height of the lowest ground at `x`. Above holes, that is the lowest ground of the level.
+    ///
+    /// The rider has fallen once the body is below it.
+    pub fn ground_height(&self, x: f32) -> f32 {
+        let flat = BOULDER_HEIGTH * PHYSICS_SCALE;
+        let on_flat_ground = self
+            .ground_segments()
+            .iter()
+            .any(|[start, end]| *start <= x && x <= *end);
+        let heights: Vec<f32> = self
+            .terrain
+            .iter()
+            .filter_map(|terrain| terrain.height_at(x))
+            .chain(if on_flat_ground { Some(flat) } else { None })
+            .collect();
+        if heights.is_empty() {
+            return self
+                .terrain
+                .iter()
+                .flat_map(Terrain::surface)
+                .fold(flat, |lowest, point| lowest.min(point[1]));
         }
+        heights.into_iter().fold(f32::INFINITY, f32::min)
     }
+}
 
-    pub fn next(&self) -> Level {
+impl Terrain {
+    /// Corners of the surface from left to right
+    pub fn surface(&self) -> Vec<[f32; 2]> {
         match self {
-            Level::Tutorial => Level::First,
-            Level::First => Level::Second,
-            Level::Second => Level::Third,
-            Level::Third => Level::Tutorial,
+            Terrain::Polyline(points) => points.clone(),
+            Terrain::Heightfield {
+                start,
+                end,
+                heights,
+            } => {
+                let step = (end - start) / (heights.len().max(2) - 1) as f32;
+                heights
+                    .iter()
+                    .enumerate()
+                    .map(|(index, height)| [start + index as f32 * step, *height])
+                    .collect()
+            }
         }
     }
 
-    pub fn colliders(&self) -> Vec<ColliderBundle> {
-        let mut colliders = vec![];
+    /// Height of the lowest surface at `x`, if the terrain reaches that far
+    pub fn height_at(&self, x: f32) -> Option<f32> {
+        self.surface()
+            .windows(2)
+            .filter_map(|segment| {
+                let ([x1, y1], [x2, y2]) = (segment[0], segment[1]);
+                if x < x1.min(x2) || x > x1.max(x2) {
+                    return None;
+                }
+                if x1 == x2 {
+                    return Some(y1.min(y2));
+                }
+                Some(y1 + (x - x1) / (x2 - x1) * (y2 - y1))
+            })
+            .fold(None, |lowest: Option<f32>, height| {
+                Some(lowest.map_or(height, |lowest| lowest.min(height)))
+            })
+    }
+
+    /// `None` for terrain with less than two points on its surface
+    pub fn collider(&self) -> Option<ColliderBundle> {
         match self {
-            Level::Tutorial => {
-                colliders.push(build_collider(
-                    Isometry::from(Point2::from([800.0 / PHYSICS_SCALE, 2.])),
-                    ColliderShape::cuboid(2., 1.),
-                ));
-            }
-            Level::First => {
-                colliders.push(build_collider(
-                    Isometry::from(Point2::from([800.0 / PHYSICS_SCALE, 2.])),
-                    ColliderShape::cuboid(2., 1.),
-                ));
-                colliders.push(build_collider(
-                    Isometry::from(Point2::from([(800.0 / PHYSICS_SCALE) * 1.8, 2.])),
-                    ColliderShape::cuboid(2., 1.),
-                ));
-            }
-            Level::Second => {
-                colliders.push(build_collider(
-                    Isometry2::new(
-                        [800.0 / PHYSICS_SCALE, 2.].into(),
-                        std::f32::consts::FRAC_PI_4,
+            Terrain::Polyline(points) if points.len() > 1 => Some(build_collider(
+                Isometry2::identity(),
+                ColliderShape::polyline(
+                    points
+                        .iter()
+                        .map(|point| Point2::from(to_physics(*point)))
+                        .collect(),
+                    None,
+                ),
+            )),
+            Terrain::Heightfield {
+                start,
+                end,
+                heights,
+            } if heights.len() > 1 => Some(build_collider(
+                Isometry2::translation((start + end) / 2. / PHYSICS_SCALE, 0.),
+                ColliderShape::heightfield(
+                    DVector::from_iterator(
+                        heights.len(),
+                        heights.iter().map(|height| height / PHYSICS_SCALE),
                     ),
-                    ColliderShape::cuboid(4., 1.),
-                ));
-            }
-            Level::Third => {
-                colliders.push(build_collider(
-                    Isometry2::new(
-                        [800.0 / PHYSICS_SCALE, 2.].into(),
-                        std::f32::consts::FRAC_PI_4,
-                    ),
-                    ColliderShape::cuboid(4., 1.),
-                ));
-                colliders.push(build_collider(
-                    Isometry2::new(
-                        [1250.0 / PHYSICS_SCALE, 2.].into(),
-                        -std::f32::consts::FRAC_PI_4,
-                    ),
-                    ColliderShape::cuboid(4., 1.),
-                ));
-            }
+                    Vector::new((end - start) / PHYSICS_SCALE, 1.),
+                ),
+            )),
+            _ => None,
         }
+    }
+}
 
-        colliders
+impl Obstacle {
+    pub fn collider(&self) -> ColliderBundle {
+        build_collider(
+            Isometry2::new(to_physics(self.position).into(), self.rotation),
+            match self.shape {
+                ObstacleShape::Cuboid {
+                    half_width,
+                    half_height,
+                } => ColliderShape::cuboid(half_width / PHYSICS_SCALE, half_height / PHYSICS_SCALE),
+                ObstacleShape::Ball { radius } => ColliderShape::ball(radius / PHYSICS_SCALE),
+            },
+        )
     }
 }
 
+impl FromWorld for Level {
+    fn from_world(world: &mut World) -> Self {
+        let cell = world.cell();
+        let level_assets = cell
+            .get_resource::<LevelAssets>()
+            .expect("LevelAssets not loaded");
+        let levels = cell
+            .get_resource::<Assets<Level>>()
+            .expect("Level assets missing");
+        levels
+            .get(&level_assets.tutorial)
+            .expect("Tutorial level not loaded")
+            .clone()
+    }
+}
+
+/// Convert a position in pixels to physics units
+pub fn to_physics(position: [f32; 2]) -> [f32; 2] {
+    [position[0] / PHYSICS_SCALE, position[1] / PHYSICS_SCALE]
+}
+
+/// The built-in levels in the order they are played
+pub fn level_order<'a>(level_assets: &LevelAssets, levels: &'a Assets<Level>) -> Vec<&'a Level> {
+    level_assets
+        .all()
+        .iter()
+        .map(|handle| levels.get(handle).expect("Level not loaded"))
+        .collect()
+}
+
+/// The level unlocked by finishing the given one. There is none after the last built-in level.
+fn following_level(
+    level: &Level,
+    level_assets: &LevelAssets,
+    levels: &Assets<Level>,
+) -> Option<Level> {
+    let order = level_order(level_assets, levels);
+    order
+        .iter()
+        .position(|candidate| candidate.id == level.id)
+        .and_then(|index| order.get(index + 1))
+        .map(|level| (*level).clone())
+}
+
 impl Plugin for LevelsPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(Level::Tutorial)
-            .add_system_set(
-                SystemSet::on_update(GameState::Prepare).with_system(prepare_level.system()),
-            )
-            .add_system_set(
-                SystemSet::on_enter(GameState::PrepareLevel).with_system(build_parcours.system()),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::PrepareLevel).with_system(start_level.system()),
-            )
-            .add_system_set(
-                SystemSet::on_exit(GameState::InLevel).with_system(clear_level.system()),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::InLevel)
-                    .with_system(restart.system())
-                    .with_system(cross_finish_line.system())
-                    .with_system(fall.system()),
-            )
-            .add_system_set(
-                SystemSet::on_enter(GameState::Finished).with_system(show_finished_button.system()),
-            )
-            .add_system_set(
-                SystemSet::on_update(GameState::Finished).with_system(next_level.system()),
-            );
+        app.add_system_set(
+            SystemSet::on_update(GameState::Prepare).with_system(prepare_level.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::PrepareLevel).with_system(build_parcours.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::PrepareLevel).with_system(start_level.system()),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::InLevel).with_system(clear_level.system()))
+        .add_system_set_to_stage(
+            GameStage::PhysicsStep,
+            SystemSet::new()
+                .with_run_criteria(riding.system())
+                .after(ActionsSystem::Replay)
+                .before(PhysicsSystems::StepWorld)
+                .with_system(restart.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::InLevel)
+                .with_system(cross_finish_line.system())
+                .with_system(fall.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Finished).with_system(show_finished_button.system()),
+        )
+        .add_system_set(SystemSet::on_update(GameState::Finished).with_system(next_level.system()));
     }
 }
 
@@ -212,7 +418,7 @@ fn cross_finish_line(
 ) {
     let body_transform = body_query.single_mut().unwrap();
 
-    if body_transform.translation.x > level.finish_line() {
+    if !level.endless && body_transform.translation.x > level.finish_line {
         // make sure win + lose in one frame don't crash the game...
         state.overwrite_push(GameState::Finished).unwrap();
         sound_effects.send(PlaySoundEffect::Won);
@@ -221,20 +427,22 @@ fn cross_finish_line(
 
 pub fn reset_level(
     level: &Level,
-    (wheel_velocity, mut wheel_position): (&mut RigidBodyVelocity, &mut RigidBodyPosition),
-    (body_velocity, mut body_position): (&mut RigidBodyVelocity, &mut RigidBodyPosition),
-    (head_velocity, mut head_position): (&mut RigidBodyVelocity, &mut RigidBodyPosition),
+    (wheel_velocity, wheel_position): (&mut RigidBodyVelocity, &mut RigidBodyPosition),
+    (body_velocity, body_position): (&mut RigidBodyVelocity, &mut RigidBodyPosition),
+    (head_velocity, head_position): (&mut RigidBodyVelocity, &mut RigidBodyPosition),
 ) {
-    let starting_points = level.get_starting_points();
+    let wheel_start = Isometry::from(Point2::from(to_physics(level.start.wheel)));
+    let body_start = Isometry::from(Point2::from(to_physics(level.start.body)));
+    let head_start = Isometry::from(Point2::from(to_physics(level.start.head)));
     *wheel_velocity = RigidBodyVelocity::default();
-    wheel_position.position = Isometry::from(starting_points.wheel);
-    wheel_position.next_position = Isometry::from(starting_points.wheel);
+    wheel_position.position = wheel_start;
+    wheel_position.next_position = wheel_start;
     *body_velocity = RigidBodyVelocity::default();
-    body_position.position = Isometry::from(starting_points.body);
-    body_position.next_position = Isometry::from(starting_points.body);
+    body_position.position = body_start;
+    body_position.next_position = body_start;
     *head_velocity = RigidBodyVelocity::default();
-    head_position.position = Isometry::from(starting_points.head);
-    head_position.next_position = Isometry::from(starting_points.head);
+    head_position.position = head_start;
+    head_position.next_position = head_start;
 }
 
 fn next_level(
@@ -252,13 +460,16 @@ fn next_level(
         (With<Head>, Without<Wheel>, Without<Body>),
     >,
     mut level: ResMut<Level>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
     button_materials: Res<ButtonMaterials>,
     mut state: ResMut<State<GameState>>,
     mut interaction_query: Query<ButtonInteraction, With<Button>>,
     text_query: Query<Entity, With<Text>>,
     input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
 ) {
-    let restart = input.just_pressed(KeyCode::R);
+    let restart = GameControl::Restart.just_pressed(&bindings, &input);
     for (button, interaction, mut material, children) in interaction_query.iter_mut() {
         let text = text_query.get(children[0]).unwrap();
         if restart {
@@ -278,10 +489,15 @@ fn next_level(
         }
         match *interaction {
             Interaction::Clicked => {
-                let next_level = level.next();
-                *level = next_level;
                 commands.entity(button).despawn();
                 commands.entity(text).despawn();
+                *level = match following_level(&level, &level_assets, &levels) {
+                    Some(next) => next,
+                    None => {
+                        state.replace(GameState::LevelSelect).unwrap();
+                        return;
+                    }
+                };
                 state.replace(GameState::PrepareLevel).unwrap();
                 let (mut wheel_velocity, mut wheel_position) = wheel_query.single_mut().unwrap();
                 let (mut body_velocity, mut body_position) = body_query.single_mut().unwrap();
@@ -311,8 +527,10 @@ fn show_finished_button(
     font_assets: Res<FontAssets>,
     button_materials: Res<ButtonMaterials>,
     level: Res<Level>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
 ) {
-    let is_last_level = *level == Level::last();
+    let has_next_level = following_level(&level, &level_assets, &levels).is_some();
     commands
         .spawn_bundle(ButtonBundle {
             style: Style {
@@ -330,10 +548,10 @@ fn show_finished_button(
             parent.spawn_bundle(TextBundle {
                 text: Text {
                     sections: vec![TextSection {
-                        value: if is_last_level {
-                            "Restart".to_string()
-                        } else {
+                        value: if has_next_level {
                             "Next!".to_string()
+                        } else {
+                            "Levels".to_string()
                         },
                         style: TextStyle {
                             font: font_assets.fira_sans.clone(),
@@ -351,15 +569,21 @@ fn show_finished_button(
 fn build_parcours(mut commands: Commands, level: Res<Level>) {
     let mut colliders = level.colliders();
     for collider in colliders.drain(..) {
-        commands
-            .spawn_bundle(collider)
-            .insert(ColliderDebugRender::default())
-            .insert(ColliderPositionSync::Discrete)
-            .insert(Platform)
-            .insert(ForLevel);
+        spawn_platform(&mut commands, collider, level.theme.platform_color());
     }
 }
 
+pub fn spawn_platform(commands: &mut Commands, collider: ColliderBundle, color: Color) -> Entity {
+    commands
+        .spawn_bundle(collider)
+        .insert(ColliderDebugRender::from(color))
+        .insert(ColliderPositionSync::Discrete)
+        .insert(Scenery::Platform)
+        .insert(Platform)
+        .insert(ForLevel)
+        .id()
+}
+
 fn build_collider(isometry: Isometry2<f32>, shape: ColliderShape) -> ColliderBundle {
     ColliderBundle {
         shape,
@@ -370,13 +594,47 @@ fn build_collider(isometry: Isometry2<f32>, shape: ColliderShape) -> ColliderBun
 
 fn fall(
     mut body_query: Query<&RigidBodyPosition, With<Body>>,
+    level: Res<Level>,
     mut state: ResMut<State<GameState>>,
     mut sound_effects: EventWriter<PlaySoundEffect>,
+    mut impacts: EventWriter<Impact>,
 ) {
     let body_transform = body_query.single_mut().unwrap();
 
-    if body_transform.position.translation.y < BOULDER_HEIGTH {
+    let position = body_transform.position.translation.vector * PHYSICS_SCALE;
+    if position.y < level.ground_height(position.x) {
         sound_effects.send(PlaySoundEffect::Fall);
+        impacts.send(Impact::Fall);
         state.push(GameState::Lost).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::generate_level;
+
+    #[test]
+    fn ordered_holes_within_the_ground_are_valid() {
+        let mut level = generate_level(0, 0.5);
+        level.holes = vec![[100., 200.], [300., 400.]];
+
+        assert!(level.validate().is_ok());
+    }
+
+    #[test]
+    fn reversed_ground_is_invalid() {
+        let mut level = generate_level(0, 0.5);
+        level.holes = vec![];
+        level.ground = [400., -400.];
+
+        assert!(level.validate().is_err());
+    }
+
+    #[test]
+    fn overlapping_holes_are_invalid() {
+        let mut level = generate_level(0, 0.5);
+        level.holes = vec![[100., 300.], [200., 400.]];
+
+        assert!(level.validate().is_err());
+    }
+}
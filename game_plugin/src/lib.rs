@@ -1,46 +1,148 @@
+use bevy::asset::AssetPlugin;
+use bevy::input::InputPlugin;
 use bevy::prelude::*;
+use bevy::transform::TransformPlugin;
 use bevy_rapier2d::prelude::*;
 
 pub struct GamePlugin;
 
 mod actions;
 mod audio;
+mod autopilot;
+mod background;
+mod camera;
+mod controls;
+mod editor;
+mod endless;
+mod generator;
+mod ghost;
+mod gym;
+mod hud;
+mod level_select;
 mod levels;
 mod loading;
 mod lost;
 mod menu;
+mod pause;
+mod physics;
 mod player;
+mod progress;
+mod replay;
+mod save;
+mod scenery;
+mod settings;
+mod speedrun;
+mod theme;
+mod touch;
 
 use crate::actions::ActionsPlugin;
-use crate::audio::InternalAudioPlugin;
+use crate::audio::{InternalAudioPlugin, PlaySoundEffect};
+use crate::autopilot::AutopilotPlugin;
+use crate::background::BackgroundPlugin;
+use crate::camera::CameraPlugin;
+use crate::controls::ControlsPlugin;
+use crate::editor::EditorPlugin;
+use crate::endless::EndlessPlugin;
+use crate::ghost::GhostPlugin;
+use crate::hud::HudPlugin;
+use crate::level_select::LevelSelectPlugin;
 use crate::levels::LevelsPlugin;
 use crate::lost::LostPlugin;
 use crate::menu::MenuPlugin;
+use crate::pause::PausePlugin;
+use crate::physics::{PhysicsClock, PhysicsPlugin};
 use crate::player::PlayerPlugin;
-use loading::LoadingPlugin;
+use crate::progress::ProgressPlugin;
+use crate::replay::ReplayPlugin;
+use crate::save::SavePlugin;
+use crate::scenery::SceneryPlugin;
+use crate::settings::SettingsPlugin;
+use crate::speedrun::SpeedrunPlugin;
+use crate::theme::ThemePlugin;
+use crate::touch::TouchPlugin;
+use loading::{HeadlessLoadingPlugin, LoadingPlugin};
+
+pub use crate::actions::{Actions, InputSource};
+pub use crate::gym::{serve, Gym, GymAction, Observation, PartState, Step};
+pub use crate::levels::{Level, Terrain};
+pub use crate::loading::LevelAssets;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_state(GameState::Loading)
-            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-            .add_plugin(RapierRenderPlugin)
+        add_gameplay_plugins(app);
+        app.add_plugin(RapierRenderPlugin)
+            .add_plugin(SceneryPlugin)
             .add_plugin(LoadingPlugin)
-            .add_plugin(ActionsPlugin)
-            .add_plugin(PlayerPlugin)
+            .add_plugin(TouchPlugin)
             .add_plugin(InternalAudioPlugin)
-            .add_plugin(LevelsPlugin)
-            .add_plugin(LostPlugin)
-            .add_plugin(MenuPlugin);
+            .add_plugin(GhostPlugin)
+            .add_plugin(SavePlugin);
     }
 }
 
+/// The game without window, audio or rendering, e.g. to drive it from tests
+///
+/// Add it next to `MinimalPlugins`. Only the levels are loaded from the assets folder and
+/// nothing is read from or written to the player's save data.
+pub struct HeadlessGamePlugin;
+
+/// Marks an app that was built with [`HeadlessGamePlugin`]
+struct Headless;
+
+impl Plugin for HeadlessGamePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Headless)
+            // as fast as possible, and every update is exactly one step of the simulation
+            .insert_resource(PhysicsClock::step_per_update())
+            .add_plugin(TransformPlugin)
+            .add_plugin(InputPlugin)
+            .add_plugin(AssetPlugin)
+            .add_asset::<ColorMaterial>()
+            .add_event::<PlaySoundEffect>()
+            .add_plugin(HeadlessLoadingPlugin);
+        add_gameplay_plugins(app);
+    }
+}
+
+/// Everything from the state machine over the levels to the rider's physics
+fn add_gameplay_plugins(app: &mut AppBuilder) {
+    app.add_state(GameState::Loading)
+        .add_plugin(PhysicsPlugin)
+        .add_plugin(ActionsPlugin)
+        .add_plugin(AutopilotPlugin)
+        .add_plugin(PlayerPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(BackgroundPlugin)
+        .add_plugin(ThemePlugin)
+        .add_plugin(LevelsPlugin)
+        .add_plugin(LostPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(PausePlugin)
+        .add_plugin(LevelSelectPlugin)
+        .add_plugin(ProgressPlugin)
+        .add_plugin(SpeedrunPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(ControlsPlugin)
+        .add_plugin(EditorPlugin)
+        .add_plugin(EndlessPlugin)
+        .add_plugin(HudPlugin);
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
-enum GameState {
+pub enum GameState {
     Loading,
+    LoadingFailed,
     Menu,
+    LevelSelect,
+    Settings,
+    Controls,
+    Credits,
     Prepare,
     PrepareLevel,
     InLevel,
+    Paused,
     Lost,
     Finished,
+    Editor,
 }
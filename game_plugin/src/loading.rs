@@ -1,4 +1,8 @@
+use crate::levels::Level;
 use crate::GameState;
+use bevy::asset::{
+    AssetLoader as BevyAssetLoader, BoxedFuture, LoadContext, LoadState, LoadedAsset,
+};
 use bevy::prelude::*;
 use bevy_asset_loader::{AssetCollection, AssetLoader};
 use bevy_kira_audio::AudioSource;
@@ -7,15 +11,259 @@ pub struct LoadingPlugin;
 
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<Level>().init_asset_loader::<LevelLoader>();
         AssetLoader::new(GameState::Loading, GameState::Menu)
             .with_collection::<FontAssets>()
             .with_collection::<AudioAssets>()
             .with_collection::<TextureAssets>()
+            .with_collection::<LevelAssets>()
+            .init_resource::<Level>()
             .build(app);
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Loading)
+                .with_system(track_loading.exclusive_system())
+                .with_system(show_loading_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Loading).with_system(update_progress.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Loading).with_system(despawn_loading_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::LoadingFailed).with_system(show_error_screen.system()),
+        );
+    }
+}
+
+/// Loads only the levels and hands out placeholder handles for everything that is rendered or played
+///
+/// Used by [`crate::HeadlessGamePlugin`], where there is no renderer or audio backend to load them for.
+pub struct HeadlessLoadingPlugin;
+
+impl Plugin for HeadlessLoadingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            // the collections look up texture atlases, even if they have none
+            .add_asset::<TextureAtlas>()
+            .init_resource::<FontAssets>()
+            .init_resource::<AudioAssets>()
+            .init_resource::<TextureAssets>();
+        AssetLoader::new(GameState::Loading, GameState::Menu)
+            .with_collection::<LevelAssets>()
+            .init_resource::<Level>()
+            .build(app);
+    }
+}
+
+/// Handles of all asset collections, used to show the loading progress
+///
+/// Loading the same paths again as the collections do only hands out the existing handles.
+struct LoadingProgress {
+    handles: Vec<HandleUntyped>,
+}
+
+/// Path of the asset that could not be loaded
+struct LoadingError(String);
+
+struct LoadingScreen;
+
+struct ProgressBar;
+
+const FONT_PATH: &str = "fonts/FiraSans-Bold.ttf";
+
+fn track_loading(world: &mut World) {
+    let mut handles = FontAssets::load(world);
+    handles.append(&mut AudioAssets::load(world));
+    handles.append(&mut TextureAssets::load(world));
+    handles.append(&mut LevelAssets::load(world));
+    world.insert_resource(LoadingProgress { handles });
+}
+
+fn show_loading_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(LoadingScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "Loading",
+                    TextStyle {
+                        font: asset_server.load(FONT_PATH),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(400.0), Val::Px(30.0)),
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::rgb(0.9, 0.9, 0.9).into()),
+                            ..Default::default()
+                        })
+                        .insert(ProgressBar);
+                });
+        });
+}
+
+fn update_progress(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    progress: Option<Res<LoadingProgress>>,
+    mut state: ResMut<State<GameState>>,
+    mut bar_query: Query<&mut Style, With<ProgressBar>>,
+) {
+    let progress = match progress {
+        Some(progress) => progress,
+        None => return,
+    };
+    let mut loaded = 0;
+    for handle in progress.handles.iter() {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => {
+                let path = asset_server
+                    .get_handle_path(handle)
+                    .map(|path| path.path().display().to_string())
+                    .unwrap_or_else(|| "unknown asset".to_string());
+                error!("Failed to load {}", path);
+                commands.insert_resource(LoadingError(path));
+                state.set(GameState::LoadingFailed).unwrap();
+                return;
+            }
+            _ => (),
+        }
+    }
+    for mut style in bar_query.iter_mut() {
+        style.size.width = Val::Percent(100. * loaded as f32 / progress.handles.len() as f32);
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, screens: Query<Entity, With<LoadingScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn show_error_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    error: Res<LoadingError>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgb(0.3, 0.05, 0.05).into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for (line, font_size) in [
+                ("Failed to load".to_string(), 40.0),
+                (error.0.clone(), 30.0),
+            ]
+            .iter()
+            {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        line.clone(),
+                        TextStyle {
+                            font: asset_server.load(FONT_PATH),
+                            font_size: *font_size,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl BevyAssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let level = ron::de::from_bytes::<Level>(bytes)?;
+            level.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
     }
 }
 
 #[derive(AssetCollection)]
+pub struct LevelAssets {
+    #[asset(path = "levels/tutorial.level.ron")]
+    pub tutorial: Handle<Level>,
+    #[asset(path = "levels/first.level.ron")]
+    pub first: Handle<Level>,
+    #[asset(path = "levels/second.level.ron")]
+    pub second: Handle<Level>,
+    #[asset(path = "levels/third.level.ron")]
+    pub third: Handle<Level>,
+}
+
+impl LevelAssets {
+    /// All built-in levels in the order they are played
+    pub fn all(&self) -> Vec<Handle<Level>> {
+        vec![
+            self.tutorial.clone(),
+            self.first.clone(),
+            self.second.clone(),
+            self.third.clone(),
+        ]
+    }
+}
+
+#[derive(AssetCollection, Default)]
 pub struct AudioAssets {
     #[asset(path = "audio/jump_1.ogg")]
     pub jump_1: Handle<AudioSource>,
@@ -35,13 +283,13 @@ pub struct AudioAssets {
     pub background: Handle<AudioSource>,
 }
 
-#[derive(AssetCollection)]
+#[derive(AssetCollection, Default)]
 pub struct FontAssets {
     #[asset(path = "fonts/FiraSans-Bold.ttf")]
     pub fira_sans: Handle<Font>,
 }
 
-#[derive(AssetCollection)]
+#[derive(AssetCollection, Default)]
 pub struct TextureAssets {
     #[asset(path = "textures/wheel.png")]
     pub wheel: Handle<Texture>,
@@ -49,6 +297,14 @@ pub struct TextureAssets {
     pub head: Handle<Texture>,
     #[asset(path = "textures/body.png")]
     pub body: Handle<Texture>,
+    #[asset(path = "textures/ground.png")]
+    pub ground: Handle<Texture>,
+    #[asset(path = "textures/ground_edge.png")]
+    pub ground_edge: Handle<Texture>,
+    #[asset(path = "textures/platform.png")]
+    pub platform: Handle<Texture>,
+    #[asset(path = "textures/boulder.png")]
+    pub boulder: Handle<Texture>,
     #[asset(path = "textures/background_1.png")]
     pub background_1: Handle<Texture>,
     #[asset(path = "textures/background_2.png")]
@@ -1,4 +1,5 @@
 use crate::audio::PlaySoundEffect;
+use crate::camera::Impact;
 use crate::levels::{reset_level, Level};
 use crate::loading::FontAssets;
 use crate::player::*;
@@ -32,6 +33,7 @@ fn lost(
     platform_query: Query<Entity, (With<Platform>, Without<Head>)>,
     narrow_phase: Res<NarrowPhase>,
     mut sounds: EventWriter<PlaySoundEffect>,
+    mut impacts: EventWriter<Impact>,
     mut state: ResMut<State<GameState>>,
 ) {
     if let Ok(head) = head_query.single_mut() {
@@ -40,6 +42,7 @@ fn lost(
             {
                 if contact_pair.has_any_active_contact {
                     sounds.send(PlaySoundEffect::Loose);
+                    impacts.send(Impact::Crash);
                     state.overwrite_push(GameState::Lost).unwrap();
                     return;
                 }
@@ -100,6 +103,9 @@ fn restart(
 pub struct ButtonMaterials {
     pub normal: Handle<ColorMaterial>,
     pub hovered: Handle<ColorMaterial>,
+    pub none: Handle<ColorMaterial>,
+    /// Dims the game behind an overlay menu
+    pub overlay: Handle<ColorMaterial>,
 }
 
 impl FromWorld for ButtonMaterials {
@@ -108,6 +114,8 @@ impl FromWorld for ButtonMaterials {
         ButtonMaterials {
             normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
             hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+            none: materials.add(Color::NONE.into()),
+            overlay: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
         }
     }
 }
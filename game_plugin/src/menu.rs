@@ -1,14 +1,394 @@
+use crate::actions::ConnectedGamepads;
+use crate::loading::FontAssets;
+use crate::lost::ButtonMaterials;
 use crate::GameState;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::app::AppExit;
+use bevy::ecs::component::Component;
 use bevy::prelude::*;
 
 pub struct MenuPlugin;
 
+/// States that show a menu screen navigable with mouse, keyboard and gamepad
+const MENU_STATES: [GameState; 6] = [
+    GameState::Menu,
+    GameState::LevelSelect,
+    GameState::Settings,
+    GameState::Controls,
+    GameState::Credits,
+    GameState::Paused,
+];
+
+/// Menu screens that lead back to the title menu
+const SUB_MENU_STATES: [GameState; 3] = [
+    GameState::LevelSelect,
+    GameState::Settings,
+    GameState::Credits,
+];
+
+/// Left stick deflection needed to move the focus
+const STICK_THRESHOLD: f32 = 0.5;
+
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(SystemSet::on_enter(GameState::Menu).with_system(start_game.system()));
+        app.init_resource::<MenuFocus>()
+            .init_resource::<SubMenuOrigin>()
+            .add_event::<ButtonActivated>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu).with_system(show_title_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu).with_system(title_menu_actions.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Credits).with_system(show_credits.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Credits).with_system(credits_actions.system()),
+            );
+        for state in MENU_STATES.iter() {
+            app.add_system_set(
+                SystemSet::on_enter(state.clone()).with_system(reset_focus.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(state.clone())
+                    .with_system(navigate_menu.system())
+                    .with_system(highlight_menu_buttons.system()),
+            )
+            .add_system_set(SystemSet::on_exit(state.clone()).with_system(despawn_menu.system()))
+            .add_system_set(SystemSet::on_pause(state.clone()).with_system(despawn_menu.system()))
+            .add_system_set(SystemSet::on_resume(state.clone()).with_system(reset_focus.system()));
+        }
+        for state in SUB_MENU_STATES.iter() {
+            app.add_system_set(
+                SystemSet::on_update(state.clone()).with_system(back_to_title.system()),
+            );
+        }
+    }
+}
+
+/// Root node of a menu screen. It is removed when leaving the screen's state.
+pub struct MenuScreen;
+
+/// A button in a menu screen. Keyboard and gamepad move the focus in the order of `index`.
+pub struct MenuButton {
+    pub index: usize,
+}
+
+#[derive(Default)]
+pub struct MenuFocus {
+    pub index: usize,
+    /// Ignore all menu input, e.g. while a screen waits for a key press
+    pub locked: bool,
+    stick_held: bool,
+}
+
+/// The screen a sub menu was opened from and leads back to
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SubMenuOrigin {
+    #[default]
+    Title,
+    /// The pause menu, still on the state stack below the sub menu
+    Pause,
+}
+
+/// Sent when a menu button is clicked or activated with keyboard or gamepad
+pub struct ButtonActivated(pub Entity);
+
+#[derive(Clone, Copy)]
+enum TitleButton {
+    Play,
+    LevelSelect,
+    Settings,
+    Credits,
+    #[cfg(not(target_arch = "wasm32"))]
+    Quit,
+}
+
+#[derive(Clone, Copy)]
+enum CreditsButton {
+    Back,
+}
+
+/// Spawn a menu screen with a title, optional lines of text and a column of buttons
+///
+/// Every button gets its action component, so screens can react to [`ButtonActivated`] events.
+pub fn spawn_menu<A: Component>(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    button_materials: &ButtonMaterials,
+    title: &str,
+    buttons: Vec<(String, A)>,
+) -> Entity {
+    spawn_menu_with_text(commands, font_assets, button_materials, title, &[], buttons)
+}
+
+pub fn spawn_menu_with_text<A: Component>(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    button_materials: &ButtonMaterials,
+    title: &str,
+    lines: &[&str],
+    buttons: Vec<(String, A)>,
+) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.none.clone(),
+            ..Default::default()
+        })
+        .insert(MenuScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text(font_assets, title, 60.0));
+            for line in lines {
+                parent.spawn_bundle(menu_text(font_assets, line, 25.0));
+            }
+            for (index, (label, action)) in buttons.into_iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(400.0), Val::Px(50.0)),
+                            margin: Rect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(MenuButton { index })
+                    .insert(action)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(menu_text(font_assets, &label, 30.0));
+                    });
+            }
+        })
+        .id()
+}
+
+pub fn menu_text(font_assets: &FontAssets, value: &str, font_size: f32) -> TextBundle {
+    TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(5.0)),
+            ..Default::default()
+        },
+        text: Text {
+            sections: vec![TextSection {
+                value: value.to_string(),
+                style: TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            }],
+            alignment: Default::default(),
+        },
+        ..Default::default()
+    }
+}
+
+fn reset_focus(mut focus: ResMut<MenuFocus>) {
+    *focus = MenuFocus::default();
+}
+
+fn despawn_menu(mut commands: Commands, screens: Query<Entity, With<MenuScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn navigate_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<ConnectedGamepads>,
+    mut focus: ResMut<MenuFocus>,
+    buttons: Query<(Entity, &MenuButton)>,
+    interactions: Query<(Entity, &MenuButton, &Interaction), Changed<Interaction>>,
+    mut activated: EventWriter<ButtonActivated>,
+) {
+    if focus.locked {
+        return;
+    }
+    for (entity, button, interaction) in interactions.iter() {
+        match *interaction {
+            Interaction::Clicked => {
+                focus.index = button.index;
+                activated.send(ButtonActivated(entity));
+                return;
+            }
+            Interaction::Hovered => focus.index = button.index,
+            Interaction::None => (),
+        }
+    }
+
+    let button_count = buttons.iter().count();
+    if button_count == 0 {
+        return;
+    }
+    let mut step = 0;
+    if keyboard_input.just_pressed(KeyCode::Up) || keyboard_input.just_pressed(KeyCode::W) {
+        step -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) || keyboard_input.just_pressed(KeyCode::S) {
+        step += 1;
+    }
+    let mut activate =
+        keyboard_input.just_pressed(KeyCode::Return) || keyboard_input.just_pressed(KeyCode::Space);
+    let mut stick = 0;
+    for gamepad in gamepads.0.iter() {
+        let pressed =
+            |button_type| gamepad_buttons.just_pressed(GamepadButton(*gamepad, button_type));
+        if pressed(GamepadButtonType::DPadUp) {
+            step -= 1;
+        }
+        if pressed(GamepadButtonType::DPadDown) {
+            step += 1;
+        }
+        activate |= pressed(GamepadButtonType::South);
+        let stick_y = gamepad_axes
+            .get(GamepadAxis(*gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.);
+        if stick_y > STICK_THRESHOLD {
+            stick = -1;
+        } else if stick_y < -STICK_THRESHOLD {
+            stick = 1;
+        }
+    }
+    if stick != 0 && !focus.stick_held {
+        step += stick;
+    }
+    focus.stick_held = stick != 0;
+    focus.index = (focus.index as i32 + step).rem_euclid(button_count as i32) as usize;
+
+    if activate {
+        if let Some((entity, _)) = buttons
+            .iter()
+            .find(|(_, button)| button.index == focus.index)
+        {
+            activated.send(ButtonActivated(entity));
+        }
+    }
+}
+
+fn highlight_menu_buttons(
+    focus: Res<MenuFocus>,
+    button_materials: Res<ButtonMaterials>,
+    mut buttons: Query<(&MenuButton, &mut Handle<ColorMaterial>)>,
+) {
+    for (button, mut material) in buttons.iter_mut() {
+        let target = if button.index == focus.index {
+            &button_materials.hovered
+        } else {
+            &button_materials.normal
+        };
+        if *material != *target {
+            *material = target.clone();
+        }
     }
 }
 
-fn start_game(mut state: ResMut<State<GameState>>) {
-    state.set(GameState::Prepare).unwrap();
+/// Go back to where a sub menu was opened from
+pub fn leave_sub_menu(state: &mut State<GameState>, origin: &mut SubMenuOrigin) {
+    match *origin {
+        SubMenuOrigin::Pause => state.pop().unwrap(),
+        SubMenuOrigin::Title => state.set(GameState::Menu).unwrap(),
+    }
+    *origin = SubMenuOrigin::Title;
+}
+
+/// Pressing Escape or the gamepad's east button leaves a sub menu
+fn back_to_title(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<ConnectedGamepads>,
+    mut state: ResMut<State<GameState>>,
+    mut origin: ResMut<SubMenuOrigin>,
+) {
+    let back = keyboard_input.just_pressed(KeyCode::Escape)
+        || gamepads.0.iter().any(|gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton(*gamepad, GamepadButtonType::East))
+        });
+    if back {
+        // keep the next screen from reacting to the same key press
+        keyboard_input.reset(KeyCode::Escape);
+        leave_sub_menu(&mut state, &mut origin);
+    }
+}
+
+fn show_title_menu(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+) {
+    let mut buttons = vec![
+        ("Play".to_string(), TitleButton::Play),
+        ("Level Select".to_string(), TitleButton::LevelSelect),
+        ("Settings".to_string(), TitleButton::Settings),
+        ("Credits".to_string(), TitleButton::Credits),
+    ];
+    #[cfg(not(target_arch = "wasm32"))]
+    buttons.push(("Quit".to_string(), TitleButton::Quit));
+    spawn_menu(
+        &mut commands,
+        &font_assets,
+        &button_materials,
+        "Me And My Unicycle",
+        buttons,
+    );
+}
+
+fn title_menu_actions(
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<&TitleButton>,
+    mut state: ResMut<State<GameState>>,
+    #[cfg(not(target_arch = "wasm32"))] mut exit: EventWriter<AppExit>,
+) {
+    for ButtonActivated(entity) in activated.iter() {
+        match buttons.get(*entity) {
+            Ok(TitleButton::Play) => state.set(GameState::Prepare).unwrap(),
+            Ok(TitleButton::LevelSelect) => state.set(GameState::LevelSelect).unwrap(),
+            Ok(TitleButton::Settings) => state.set(GameState::Settings).unwrap(),
+            Ok(TitleButton::Credits) => state.set(GameState::Credits).unwrap(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Ok(TitleButton::Quit) => exit.send(AppExit),
+            Err(_) => (),
+        }
+    }
+}
+
+fn show_credits(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+) {
+    spawn_menu_with_text(
+        &mut commands,
+        &font_assets,
+        &button_materials,
+        "Credits",
+        &[
+            "Made possible by Bevy",
+            "All assets and programming done by @nikl_me",
+        ],
+        vec![("Back".to_string(), CreditsButton::Back)],
+    );
+}
+
+fn credits_actions(
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<&CreditsButton>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for ButtonActivated(entity) in activated.iter() {
+        if let Ok(CreditsButton::Back) = buttons.get(*entity) {
+            state.set(GameState::Menu).unwrap();
+        }
+    }
 }
@@ -0,0 +1,142 @@
+use crate::actions::ConnectedGamepads;
+use crate::loading::FontAssets;
+use crate::lost::ButtonMaterials;
+use crate::menu::{spawn_menu, ButtonActivated, SubMenuOrigin};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(SystemSet::on_update(GameState::InLevel).with_system(pause.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused)
+                    .with_system(freeze_physics.system())
+                    .with_system(show_pause_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_resume(GameState::Paused).with_system(show_pause_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused).with_system(pause_menu_actions.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused).with_system(unfreeze_physics.system()),
+            );
+        #[cfg(target_arch = "wasm32")]
+        app.add_system_set(
+            SystemSet::on_update(GameState::InLevel).with_system(pause_on_focus_loss.system()),
+        );
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Restart,
+    LevelSelect,
+    Settings,
+}
+
+/// Escape or the gamepad's start button toggle the pause menu
+fn toggle_pressed(
+    keyboard_input: &mut Input<KeyCode>,
+    gamepad_buttons: &Input<GamepadButton>,
+    gamepads: &ConnectedGamepads,
+) -> bool {
+    let pressed = keyboard_input.just_pressed(KeyCode::Escape)
+        || gamepads.0.iter().any(|gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton(*gamepad, GamepadButtonType::Start))
+        });
+    if pressed {
+        // the new state should not see the same key press again
+        keyboard_input.reset(KeyCode::Escape);
+    }
+    pressed
+}
+
+fn pause(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<ConnectedGamepads>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if toggle_pressed(&mut keyboard_input, &gamepad_buttons, &gamepads) {
+        state.push(GameState::Paused).unwrap();
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn pause_on_focus_loss(
+    mut focus_events: EventReader<bevy::window::WindowFocused>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if focus_events.iter().any(|event| !event.focused) {
+        state.overwrite_push(GameState::Paused).unwrap();
+    }
+}
+
+/// Stop stepping the physics pipeline. Positions and velocities stay untouched until it is activated again.
+fn freeze_physics(mut configuration: ResMut<RapierConfiguration>) {
+    configuration.physics_pipeline_active = false;
+}
+
+fn unfreeze_physics(mut configuration: ResMut<RapierConfiguration>) {
+    configuration.physics_pipeline_active = true;
+}
+
+fn show_pause_menu(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+) {
+    let menu = spawn_menu(
+        &mut commands,
+        &font_assets,
+        &button_materials,
+        "Paused",
+        vec![
+            ("Resume".to_string(), PauseButton::Resume),
+            ("Restart".to_string(), PauseButton::Restart),
+            ("Level Select".to_string(), PauseButton::LevelSelect),
+            ("Settings".to_string(), PauseButton::Settings),
+        ],
+    );
+    commands
+        .entity(menu)
+        .insert(button_materials.overlay.clone());
+}
+
+fn pause_menu_actions(
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<&PauseButton>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<ConnectedGamepads>,
+    mut state: ResMut<State<GameState>>,
+    mut origin: ResMut<SubMenuOrigin>,
+) {
+    if toggle_pressed(&mut keyboard_input, &gamepad_buttons, &gamepads) {
+        state.pop().unwrap();
+        return;
+    }
+    for ButtonActivated(entity) in activated.iter() {
+        match buttons.get(*entity) {
+            Ok(PauseButton::Resume) => state.pop().unwrap(),
+            // leaving the level despawns it, so it is built from scratch again
+            Ok(PauseButton::Restart) => state.replace(GameState::PrepareLevel).unwrap(),
+            // the level is left for good, so the level select leads back to the title menu
+            Ok(PauseButton::LevelSelect) => {
+                *origin = SubMenuOrigin::Title;
+                state.replace(GameState::LevelSelect).unwrap();
+            }
+            Ok(PauseButton::Settings) => {
+                *origin = SubMenuOrigin::Pause;
+                state.push(GameState::Settings).unwrap();
+            }
+            Err(_) => (),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::GameState;
+use bevy::app::Events;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy_rapier2d::physics::{
+    self, JointsEntityMap, ModificationTracker, PhysicsStages, PhysicsSystems,
+    SimulationToRenderTime,
+};
+use bevy_rapier2d::prelude::*;
+
+/// The physics of `RapierPhysicsPlugin`, but stepped in [`GameStage::PhysicsStep`]
+pub struct PhysicsPlugin;
+
+/// Physics steps run per frame at most. The game slows down on frames taking longer.
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub enum GameStage {
+    /// Runs once for every physics step that fits into the time of the frame, right before the
+    /// update. Everything feeding [`Actions`](crate::actions::Actions) into the physics runs here.
+    ///
+    /// Entities despawned during the update are only seen by `collect_removals`, so they are not
+    /// removed from the physics twice.
+    PhysicsStep,
+}
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PhysicsClock>()
+            .add_stage_before(
+                CoreStage::PreUpdate,
+                PhysicsStages::FinalizeCreations,
+                SystemStage::parallel(),
+            )
+            .add_stage_before(
+                CoreStage::Update,
+                GameStage::PhysicsStep,
+                SystemStage::parallel().with_run_criteria(physics_steps.system()),
+            )
+            .add_stage_before(
+                CoreStage::PostUpdate,
+                PhysicsStages::SyncTransforms,
+                SystemStage::parallel(),
+            )
+            .insert_resource(PhysicsPipeline::new())
+            .insert_resource(QueryPipeline::new())
+            .insert_resource(RapierConfiguration::default())
+            .insert_resource(IntegrationParameters::default())
+            .insert_resource(BroadPhase::new())
+            .insert_resource(NarrowPhase::new())
+            .insert_resource(IslandManager::new())
+            .insert_resource(JointSet::new())
+            .insert_resource(CCDSolver::new())
+            .insert_resource(Events::<IntersectionEvent>::default())
+            .insert_resource(Events::<ContactEvent>::default())
+            .insert_resource(SimulationToRenderTime::default())
+            .insert_resource(JointsEntityMap::default())
+            .insert_resource(ModificationTracker::default())
+            .insert_resource(PhysicsHooksWithQueryObject::<NoUserData>(Box::new(())))
+            .add_system_to_stage(
+                PhysicsStages::FinalizeCreations,
+                physics::attach_bodies_and_colliders_system
+                    .system()
+                    .label(PhysicsSystems::AttachBodiesAndColliders),
+            )
+            .add_system_to_stage(
+                PhysicsStages::FinalizeCreations,
+                physics::create_joints_system
+                    .system()
+                    .label(PhysicsSystems::CreateJoints),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                physics::finalize_collider_attach_to_bodies
+                    .system()
+                    .label(PhysicsSystems::FinalizeColliderAttachToBodies),
+            )
+            .add_system_to_stage(
+                GameStage::PhysicsStep,
+                physics::step_world_system::<NoUserData>
+                    .system()
+                    .label(PhysicsSystems::StepWorld),
+            )
+            .add_system_to_stage(
+                PhysicsStages::SyncTransforms,
+                physics::sync_transforms
+                    .system()
+                    .label(PhysicsSystems::SyncTransforms),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                physics::collect_removals
+                    .system()
+                    .label(PhysicsSystems::CollectRemovals),
+            );
+    }
+}
+
+/// Decides how many physics steps run in a frame
+///
+/// Every step advances the physics by `IntegrationParameters::dt`, so the simulation keeps up
+/// with the time that passed independent of the frame rate.
+#[derive(Default)]
+pub struct PhysicsClock {
+    /// Time that passed, but was not simulated yet
+    accumulator: f32,
+    /// Set while the steps of the current frame run
+    stepping: bool,
+    /// Step exactly once per update instead of following the time, e.g. in headless runs
+    pub step_per_update: bool,
+}
+
+impl PhysicsClock {
+    pub fn step_per_update() -> Self {
+        PhysicsClock {
+            step_per_update: true,
+            ..Default::default()
+        }
+    }
+}
+
+fn physics_steps(
+    time: Res<Time>,
+    integration_parameters: Res<IntegrationParameters>,
+    mut clock: ResMut<PhysicsClock>,
+) -> ShouldRun {
+    let dt = integration_parameters.dt;
+    if !clock.stepping {
+        clock.accumulator = if clock.step_per_update {
+            dt
+        } else {
+            (clock.accumulator + time.delta_seconds()).min(MAX_STEPS_PER_FRAME as f32 * dt)
+        };
+    }
+    if clock.accumulator >= dt {
+        clock.accumulator -= dt;
+        clock.stepping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        clock.stepping = false;
+        ShouldRun::No
+    }
+}
+
+/// Run criteria for the parts of a physics step that only run while riding through a level
+pub fn riding(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::InLevel {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
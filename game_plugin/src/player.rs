@@ -1,14 +1,16 @@
-use crate::actions::Actions;
+use crate::actions::{Actions, ActionsSystem};
 use crate::audio::PlaySoundEffect;
-use crate::levels::{ForLevel, Level};
+use crate::camera::Impact;
+use crate::levels::{to_physics, ForLevel, Level};
 use crate::loading::TextureAssets;
-use crate::lost::LostSystem;
+use crate::physics::{riding, GameStage};
+use crate::scenery::Scenery;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_rapier2d::na::Point2;
+use bevy_rapier2d::physics::{PhysicsSystems, TimestepMode};
 use bevy_rapier2d::prelude::*;
 use nalgebra::Isometry2;
-use rand::Rng;
 
 pub struct PlayerPlugin;
 
@@ -19,6 +21,8 @@ pub const BODY_LENGTH: f32 = 1.;
 pub const BOULDER_HEIGTH: f32 = 1.0;
 
 pub const PHYSICS_SCALE: f32 = 32.0;
+/// Simulated seconds per physics step. Runs are replayed step by step.
+pub const PHYSICS_TIMESTEP: f32 = 1. / 60.;
 
 pub struct Wheel;
 pub struct Head;
@@ -30,33 +34,35 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.insert_resource(JumpBlock::NotBlocked)
             .insert_resource(LandBlock::NotBlocked)
-            .add_system_set(
-                SystemSet::on_enter(GameState::Prepare)
-                    .with_system(setup_rapier_and_camera.system()),
-            )
+            .add_startup_system(setup_rapier_and_camera.system())
             .add_system_set(
                 SystemSet::on_enter(GameState::PrepareLevel)
                     .with_system(prepare_player_and_platforms.system())
-                    .with_system(draw_background.system()),
+                    .with_system(draw_decorations.system()),
             )
-            .add_system_set(
-                SystemSet::on_update(GameState::InLevel)
-                    .before(LostSystem::Lost)
+            .add_system_set_to_stage(
+                GameStage::PhysicsStep,
+                SystemSet::new()
+                    .with_run_criteria(riding.system())
+                    .before(PhysicsSystems::StepWorld)
+                    .after(ActionsSystem::Replay)
                     .with_system(paddle_wheel.system())
                     .with_system(move_head.system())
-                    .with_system(move_camera.system())
                     .with_system(jump.system())
                     .with_system(landing.system()),
-            )
-            .add_system_set(SystemSet::on_update(GameState::Lost).with_system(move_camera.system()))
-            .add_system_set(
-                SystemSet::on_update(GameState::Finished).with_system(move_camera.system()),
             );
     }
 }
 
-fn setup_rapier_and_camera(mut commands: Commands, mut configuration: ResMut<RapierConfiguration>) {
+fn setup_rapier_and_camera(
+    mut commands: Commands,
+    mut configuration: ResMut<RapierConfiguration>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+) {
     configuration.scale = PHYSICS_SCALE;
+    // every run of the physics step stage advances the simulation by exactly `dt`
+    configuration.timestep_mode = TimestepMode::FixedTimestep;
+    integration_parameters.dt = PHYSICS_TIMESTEP;
 
     let mut camera = OrthographicCameraBundle::new_2d();
     camera.transform = Transform::from_translation(Vec3::new(0.0, 300.0, 0.0));
@@ -71,9 +77,9 @@ pub fn prepare_player_and_platforms(
     level: Res<Level>,
 ) {
     spawn_ground(&mut commands, &level);
-    let head_id = spawn_head(&mut commands, &textures, &mut materials);
-    let body_id = spawn_body(&mut commands, &textures, &mut materials);
-    let wheel_id = spawn_wheel(&mut commands, &textures, &mut materials);
+    let head_id = spawn_head(&mut commands, &textures, &mut materials, &level);
+    let body_id = spawn_body(&mut commands, &textures, &mut materials, &level);
+    let wheel_id = spawn_wheel(&mut commands, &textures, &mut materials, &level);
 
     let mut wheel_body_joint = BallJoint::new(
         Vec2::new(0.0, 0.0).into(),
@@ -105,7 +111,7 @@ pub fn prepare_player_and_platforms(
 }
 
 fn paddle_wheel(
-    time: Res<Time>,
+    integration_parameters: Res<IntegrationParameters>,
     actions: Res<Actions>,
     mut wheel_query: Query<&mut RigidBodyVelocity, With<Wheel>>,
 ) {
@@ -113,26 +119,15 @@ fn paddle_wheel(
         return;
     }
     let speed = 20.;
-    let movement = actions.paddling.unwrap() * speed * time.delta_seconds();
+    let movement = actions.paddling.unwrap() * speed * integration_parameters.dt;
     for mut wheel_velocity in wheel_query.iter_mut() {
         wheel_velocity.angvel = wheel_velocity.angvel - movement;
         // player_velocity.linvel.data.0[0][0] += movement.x;
     }
 }
 
-fn move_camera(
-    head_query: Query<&Transform, (With<Head>, Without<Camera>)>,
-    mut camera_query: Query<&mut Transform, (With<Camera>, Without<Head>)>,
-) {
-    for head_transform in head_query.iter() {
-        for mut camera_transform in camera_query.iter_mut() {
-            camera_transform.translation.x = head_transform.translation.x;
-        }
-    }
-}
-
 fn move_head(
-    time: Res<Time>,
+    integration_parameters: Res<IntegrationParameters>,
     actions: Res<Actions>,
     mut head_query: Query<&mut RigidBodyVelocity, With<Head>>,
 ) {
@@ -140,7 +135,7 @@ fn move_head(
         return;
     }
     let speed = 20.;
-    let movement = actions.head_balance.unwrap() * speed * time.delta_seconds();
+    let movement = actions.head_balance.unwrap() * speed * integration_parameters.dt;
     for mut head_velocity in head_query.iter_mut() {
         // head_velocity.angvel = clamp(head_velocity.angvel - movement, -5., 5.);
         head_velocity.linvel.data.0[0][0] += movement;
@@ -148,78 +143,84 @@ fn move_head(
 }
 
 fn spawn_ground(commands: &mut Commands, level: &Level) {
-    let finish_line = level.finish_line();
-    let ground_length = (finish_line + 800.) / PHYSICS_SCALE;
-    let mut border_points: Vec<f32> = level
-        .holes()
-        .iter()
-        .flat_map(|hole| vec![hole[0] / PHYSICS_SCALE, hole[1] / PHYSICS_SCALE])
-        .collect();
-    border_points.push((finish_line + 400.) / PHYSICS_SCALE);
-    border_points.insert(0, -400. / PHYSICS_SCALE);
-    let (beginning, end): (Vec<(usize, f32)>, Vec<(usize, f32)>) = border_points
-        .drain(..)
-        .enumerate()
-        .partition(|(index, _)| index % 2 == 0);
-    let mut starting_points: Vec<f32> = beginning.iter().map(|(_, value)| *value).collect();
-    let mut ending_points: Vec<f32> = end.iter().map(|(_, value)| *value).collect();
-    let borders: Vec<(f32, f32)> = starting_points
-        .drain(..)
-        .zip(ending_points.drain(..))
-        .collect();
-    for (start, end) in borders {
-        commands
-            .spawn_bundle(ColliderBundle {
-                shape: ColliderShape::cuboid((end - start) / 2., BOULDER_HEIGTH),
-                position: ColliderPosition(Isometry::from(Point2::from([
-                    start + (end - start) / 2.,
-                    0.,
-                ]))),
-                ..Default::default()
-            })
-            .insert(ColliderDebugRender::default())
-            .insert(ColliderPositionSync::Discrete)
-            .insert(Platform)
-            .insert(ForLevel);
+    let color = level.theme.ground_color();
+    for [start, end] in level.ground_segments() {
+        spawn_ground_segment(commands, start, end, color);
+    }
+    for (index, terrain) in level.terrain.iter().enumerate() {
+        match terrain.collider() {
+            // the debug render can't draw polylines or heightfields
+            Some(collider) => {
+                commands
+                    .spawn_bundle(collider)
+                    .insert(ColliderPositionSync::Discrete)
+                    .insert(Scenery::Ground)
+                    .insert(Platform)
+                    .insert(ForLevel);
+            }
+            None => warn!("Skipping terrain {} with less than two points", index),
+        }
     }
     commands
         .spawn_bundle(ColliderBundle {
             shape: ColliderShape::cuboid(300.0 / PHYSICS_SCALE, BOULDER_HEIGTH),
             position: ColliderPosition(Isometry2::new(
-                [-(400.0 / PHYSICS_SCALE), 300.0 / PHYSICS_SCALE].into(),
+                [level.ground[0] / PHYSICS_SCALE, 300.0 / PHYSICS_SCALE].into(),
                 std::f32::consts::FRAC_PI_2,
             )),
             ..Default::default()
         })
-        .insert(ColliderDebugRender::default())
+        .insert(ColliderDebugRender::from(color))
         .insert(ColliderPositionSync::Discrete)
+        .insert(Scenery::Wall)
         .insert(ForLevel);
+    if level.endless {
+        return;
+    }
     commands
         .spawn_bundle(ColliderBundle {
             shape: ColliderShape::cuboid(300.0 / PHYSICS_SCALE, BOULDER_HEIGTH),
             position: ColliderPosition(Isometry2::new(
-                [ground_length - 400. / PHYSICS_SCALE, 300.0 / PHYSICS_SCALE].into(),
+                [level.ground[1] / PHYSICS_SCALE, 300.0 / PHYSICS_SCALE].into(),
                 std::f32::consts::FRAC_PI_2,
             )),
             ..Default::default()
         })
-        .insert(ColliderDebugRender::default())
+        .insert(ColliderDebugRender::from(color))
         .insert(ColliderPositionSync::Discrete)
+        .insert(Scenery::Wall)
         .insert(ForLevel);
 }
 
+/// Spawn a piece of ground between `start` and `end` given in pixels
+pub fn spawn_ground_segment(commands: &mut Commands, start: f32, end: f32, color: Color) -> Entity {
+    let (start, end) = (start / PHYSICS_SCALE, end / PHYSICS_SCALE);
+    commands
+        .spawn_bundle(ColliderBundle {
+            shape: ColliderShape::cuboid((end - start) / 2., BOULDER_HEIGTH),
+            position: ColliderPosition(Isometry::from(Point2::from([
+                start + (end - start) / 2.,
+                0.,
+            ]))),
+            ..Default::default()
+        })
+        .insert(ColliderDebugRender::from(color))
+        .insert(ColliderPositionSync::Discrete)
+        .insert(Scenery::Ground)
+        .insert(Platform)
+        .insert(ForLevel)
+        .id()
+}
+
 fn spawn_body(
     commands: &mut Commands,
     textures: &TextureAssets,
     materials: &mut Assets<ColorMaterial>,
+    level: &Level,
 ) -> Entity {
     commands
         .spawn_bundle(RigidBodyBundle {
-            position: [
-                0.,
-                BOULDER_HEIGTH + 2. * WHEEL_RADIUS + 0.5 * BODY_LENGTH + BODY_RADIUS,
-            ]
-            .into(),
+            position: to_physics(level.start.body).into(),
             forces: RigidBodyForces {
                 gravity_scale: 0.3,
                 ..Default::default()
@@ -253,18 +254,11 @@ fn spawn_head(
     commands: &mut Commands,
     textures: &TextureAssets,
     materials: &mut Assets<ColorMaterial>,
+    level: &Level,
 ) -> Entity {
     commands
         .spawn_bundle(RigidBodyBundle {
-            position: [
-                0.,
-                0.5 * BOULDER_HEIGTH
-                    + 2. * WHEEL_RADIUS
-                    + BODY_LENGTH
-                    + 2. * BODY_RADIUS
-                    + HEAD_RADIUS,
-            ]
-            .into(),
+            position: to_physics(level.start.head).into(),
             ..Default::default()
         })
         .insert_bundle(ColliderBundle {
@@ -344,8 +338,16 @@ enum LandBlock {
 fn landing(
     mut contact_event: EventReader<ContactEvent>,
     mut sound_effects: EventWriter<PlaySoundEffect>,
+    mut impacts: EventWriter<Impact>,
     mut land_block: ResMut<LandBlock>,
+    wheel_query: Query<&RigidBodyVelocity, With<Wheel>>,
+    mut falling_speed: Local<f32>,
 ) {
+    // the contacts are from the last physics step, so the speed before that step is the impact's
+    let impact_speed = *falling_speed;
+    if let Ok(velocity) = wheel_query.single() {
+        *falling_speed = -velocity.linvel.y;
+    }
     // give it a frame until playing the next sound...
     let mut play = false;
     for event in contact_event.iter() {
@@ -359,6 +361,7 @@ fn landing(
     }
     if play {
         sound_effects.send(PlaySoundEffect::Land);
+        impacts.send(Impact::Landing(impact_speed));
         *land_block = LandBlock::Blocked;
     }
 }
@@ -367,10 +370,11 @@ fn spawn_wheel(
     commands: &mut Commands,
     textures: &TextureAssets,
     materials: &mut Assets<ColorMaterial>,
+    level: &Level,
 ) -> Entity {
     commands
         .spawn_bundle(RigidBodyBundle {
-            position: [0., 0.5 * BOULDER_HEIGTH + WHEEL_RADIUS].into(),
+            position: to_physics(level.start.wheel).into(),
             damping: RigidBodyDamping {
                 angular_damping: 0.2.into(),
                 ..RigidBodyDamping::default()
@@ -396,123 +400,23 @@ fn spawn_wheel(
         .id()
 }
 
-fn draw_background(
+fn draw_decorations(
     mut commands: Commands,
     textures: Res<TextureAssets>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     level: Res<Level>,
 ) {
-    let mut random = rand::thread_rng();
-    for slot in 0..5 {
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(
-                    {
-                        match random.gen_range(0..3) {
-                            0 => textures.background_1.clone(),
-                            1 => textures.background_2.clone(),
-                            _ => textures.background_3.clone(),
-                        }
-                    }
-                    .into(),
-                ),
-                transform: Transform::from_translation(Vec3::new(slot as f32 * 800.0, 300.0, 0.0)),
-                ..Default::default()
-            })
-            .insert(ForLevel);
-    }
-    if *level != Level::last() {
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.finish.clone().into()),
-                transform: {
-                    let mut transform =
-                        Transform::from_translation(Vec3::new(level.finish_line(), 250.0, 0.0));
-                    transform.scale = Vec3::new(0.5, 0.5, 0.5);
-
-                    transform
-                },
-                ..Default::default()
-            })
-            .insert(ForLevel);
-    } else {
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.player_won.clone().into()),
-                transform: {
-                    let mut transform =
-                        Transform::from_translation(Vec3::new(level.finish_line(), 250.0, 0.0));
-                    transform.scale = Vec3::new(0.25, 0.25, 0.25);
-
-                    transform
-                },
-                ..Default::default()
-            })
-            .insert(ForLevel);
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.thanks.clone().into()),
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(
-                        level.finish_line() + 200.,
-                        170.0,
-                        0.0,
-                    ));
-                    transform.scale = Vec3::new(0.25, 0.25, 0.25);
-
-                    transform
-                },
-                ..Default::default()
-            })
-            .insert(ForLevel);
-    }
-    if *level == Level::Tutorial {
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.tutorial.clone().into()),
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-180.0, 250.0, 0.0));
-                    transform.scale = Vec3::new(0.5, 0.5, 0.5);
-
-                    transform
-                },
-                ..Default::default()
-            })
-            .insert(ForLevel);
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.tutorial_jump.clone().into()),
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(620.0, 250.0, 0.0));
-                    transform.scale = Vec3::new(0.5, 0.5, 0.5);
-
-                    transform
-                },
-                ..Default::default()
-            })
-            .insert(ForLevel);
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.tutorial_falling.clone().into()),
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(1600.0, 250.0, 0.0));
-                    transform.scale = Vec3::new(0.5, 0.5, 0.5);
-
-                    transform
-                },
-                ..Default::default()
-            })
-            .insert(ForLevel);
+    for decoration in level.decorations.iter() {
         commands
             .spawn_bundle(SpriteBundle {
-                material: materials.add(textures.tutorial_restart.clone().into()),
+                material: materials.add(decoration.texture.texture(&textures).into()),
                 transform: {
                     let mut transform = Transform::from_translation(Vec3::new(
-                        level.finish_line() + 200.,
-                        170.0,
+                        decoration.position[0],
+                        decoration.position[1],
                         0.0,
                     ));
-                    transform.scale = Vec3::new(0.5, 0.5, 0.5);
+                    transform.scale = Vec3::splat(decoration.scale);
 
                     transform
                 },
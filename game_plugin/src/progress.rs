@@ -0,0 +1,110 @@
+use crate::actions::InputSource;
+use crate::levels::Level;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub struct ProgressPlugin;
+
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Progress>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Finished).with_system(record_finish.system()),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Lost).with_system(count_fall.system()));
+    }
+}
+
+/// What the player achieved so far
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Progress {
+    /// By level id
+    pub levels: HashMap<String, LevelProgress>,
+    /// By [`record_key`], so changing a level starts its records over
+    pub records: HashMap<String, Record>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LevelProgress {
+    pub completed: bool,
+    pub falls: u32,
+    /// Fastest finish in seconds. Only read from saves written before records were kept per level version.
+    #[serde(skip_serializing)]
+    pub best_time: Option<f32>,
+}
+
+/// Personal best of a level version
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Record {
+    /// Seconds from the first movement to crossing the finish line
+    pub time: f32,
+    /// Time at every split point, the last one being the finish line
+    pub splits: Vec<f32>,
+}
+
+pub fn record_key(id: &str, version: u32) -> String {
+    format!("{}@{}", id, version)
+}
+
+impl Progress {
+    pub fn get(&self, level: &Level) -> Option<&LevelProgress> {
+        self.levels.get(&level.id)
+    }
+
+    pub fn is_completed(&self, level: &Level) -> bool {
+        self.get(level)
+            .map(|progress| progress.completed)
+            .unwrap_or(false)
+    }
+
+    /// The first level is always unlocked. Every other one is unlocked by finishing its predecessor.
+    pub fn is_unlocked(&self, index: usize, order: &[&Level]) -> bool {
+        index == 0 || self.is_completed(order[index - 1])
+    }
+
+    pub fn complete(&mut self, level: &Level) {
+        self.levels.entry(level.id.clone()).or_default().completed = true;
+    }
+
+    pub fn record(&self, level: &Level) -> Option<&Record> {
+        self.records.get(&record_key(&level.id, level.version))
+    }
+
+    /// Keep the run if it beats the personal best of the level's current version
+    ///
+    /// Returns whether the run is a new record.
+    pub fn submit_run(&mut self, level: &Level, run: Record) -> bool {
+        let key = record_key(&level.id, level.version);
+        let new_record = self
+            .records
+            .get(&key)
+            .map(|record| run.time < record.time)
+            .unwrap_or(true);
+        if new_record {
+            self.records.insert(key, run);
+        }
+        new_record
+    }
+
+    pub fn record_fall(&mut self, level: &Level) {
+        self.levels.entry(level.id.clone()).or_default().falls += 1;
+    }
+}
+
+/// Only the player's own runs count, not those of the autopilot, a replay or test rides in the
+/// editor
+fn record_finish(level: Res<Level>, source: Res<InputSource>, mut progress: ResMut<Progress>) {
+    if !level.endless && !level.is_custom() && *source == InputSource::Player {
+        progress.complete(&level);
+    }
+}
+
+fn count_fall(level: Res<Level>, source: Res<InputSource>, mut progress: ResMut<Progress>) {
+    if !level.endless && !level.is_custom() && *source == InputSource::Player {
+        progress.record_fall(&level);
+    }
+}
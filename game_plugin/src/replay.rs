@@ -0,0 +1,235 @@
+use crate::actions::{Actions, ActionsSystem, InputSource};
+use crate::endless::{endless_level, EndlessRun};
+use crate::levels::{level_order, Level};
+use crate::loading::LevelAssets;
+use crate::physics::{riding, GameStage};
+use crate::save::write_data;
+use crate::{GameState, Headless};
+use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct ReplayPlugin;
+
+/// The last replayable run that ended by falling or finishing is kept here in the game's data
+/// directory
+const REPLAY_FILE: &str = "replays/last.ron";
+
+impl Plugin for ReplayPlugin {
+    /// Starting the game with `--replay <file>` plays that replay instead of showing the menu
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ReplayRecording>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu).with_system(start_playback.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::PrepareLevel).with_system(rewind.system()),
+            )
+            .add_system_set_to_stage(
+                GameStage::PhysicsStep,
+                SystemSet::new()
+                    .with_run_criteria(riding.system())
+                    .with_system(
+                        replay_actions
+                            .system()
+                            .label(ActionsSystem::Replay)
+                            .before(PhysicsSystems::StepWorld),
+                    ),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Lost).with_system(end_run.system()))
+            .add_system_set(SystemSet::on_enter(GameState::Finished).with_system(end_run.system()));
+        if let Some(path) = replay_argument() {
+            match load_replay(&path) {
+                Ok(replay) => {
+                    app.insert_resource(ReplayPlayback {
+                        replay,
+                        frame: 0,
+                        started: false,
+                        player_physics: None,
+                    });
+                }
+                Err(error) => error!("Failed to load replay {}: {}", path, error),
+            }
+        }
+    }
+}
+
+/// Everything needed to reproduce a run: the level, its physics and the actions of every step
+#[derive(Deserialize, Serialize)]
+pub struct Replay {
+    pub level_id: String,
+    pub level_version: u32,
+    /// Seed of the terrain in the endless mode
+    pub seed: u64,
+    /// Simulated seconds per physics step
+    pub timestep: f32,
+    pub gravity: [f32; 2],
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// One physics step of a run
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct ReplayFrame {
+    pub actions: Actions,
+    /// The physics were frozen during this step, e.g. by the hit stop of a crash
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Steps of the current attempt
+#[derive(Default)]
+struct ReplayRecording {
+    frames: Vec<ReplayFrame>,
+}
+
+/// A replay that is fed into [`Actions`] instead of the player's input
+struct ReplayPlayback {
+    replay: Replay,
+    frame: usize,
+    started: bool,
+    /// Gravity and timestep to restore once the playback ends
+    player_physics: Option<(Vector<Real>, f32)>,
+}
+
+fn replay_argument() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--replay");
+    args.next()?;
+    args.next()
+}
+
+fn load_replay(path: &str) -> anyhow::Result<Replay> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(ron::de::from_str(&content)?)
+}
+
+/// Jump from the menu right into the replayed level with the physics it was recorded with
+fn start_playback(
+    mut playback: Option<ResMut<ReplayPlayback>>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    mut level: ResMut<Level>,
+    mut run: ResMut<EndlessRun>,
+    mut source: ResMut<InputSource>,
+    mut configuration: ResMut<RapierConfiguration>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let playback = match playback.as_mut() {
+        Some(playback) if !playback.started => playback,
+        _ => return,
+    };
+    playback.started = true;
+    let replay = &playback.replay;
+    match replayable_level(&replay.level_id, &level_assets, &levels) {
+        Some(replayed) => *level = replayed,
+        None => {
+            error!("The replayed level {} does not exist", replay.level_id);
+            return;
+        }
+    }
+    run.seed = replay.seed;
+    if level.version != replay.level_version {
+        warn!(
+            "The replay was recorded on version {} of level {}, but version {} is loaded",
+            replay.level_version, level.id, level.version
+        );
+    }
+    let replay_physics = (Vec2::from(replay.gravity).into(), replay.timestep);
+    playback.player_physics = Some((configuration.gravity, integration_parameters.dt));
+    configuration.gravity = replay_physics.0;
+    integration_parameters.dt = replay_physics.1;
+    *source = InputSource::Replay;
+    state.set(GameState::Prepare).unwrap();
+}
+
+/// The built-in levels and the endless mode. Others, like levels from the editor, can not be
+/// found again to play back a replay.
+fn replayable_level(id: &str, level_assets: &LevelAssets, levels: &Assets<Level>) -> Option<Level> {
+    if id == endless_level().id {
+        return Some(endless_level());
+    }
+    level_order(level_assets, levels)
+        .into_iter()
+        .find(|level| level.id == id)
+        .cloned()
+}
+
+/// Every attempt is recorded and played back from its first frame
+fn rewind(mut recording: ResMut<ReplayRecording>, playback: Option<ResMut<ReplayPlayback>>) {
+    recording.frames.clear();
+    if let Some(mut playback) = playback {
+        playback.frame = 0;
+    }
+}
+
+/// Overwrite [`Actions`] with the next step of a playback, or record them
+fn replay_actions(
+    mut actions: ResMut<Actions>,
+    source: Res<InputSource>,
+    mut recording: ResMut<ReplayRecording>,
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut configuration: ResMut<RapierConfiguration>,
+) {
+    if let (InputSource::Replay, Some(mut playback)) = (*source, playback) {
+        let frame = playback
+            .replay
+            .frames
+            .get(playback.frame)
+            .cloned()
+            .unwrap_or_default();
+        playback.frame += 1;
+        *actions = frame.actions;
+        // steps frozen in the recorded run have to be skipped here as well
+        configuration.physics_pipeline_active = !frame.paused;
+        return;
+    }
+    recording.frames.push(ReplayFrame {
+        actions: actions.clone(),
+        paused: !configuration.physics_pipeline_active,
+    });
+}
+
+/// Save the recording of a run that ended, or hand control back to the player after a playback
+fn end_run(
+    mut commands: Commands,
+    level: Res<Level>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    run: Res<EndlessRun>,
+    mut configuration: ResMut<RapierConfiguration>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+    mut recording: ResMut<ReplayRecording>,
+    mut source: ResMut<InputSource>,
+    playback: Option<Res<ReplayPlayback>>,
+    headless: Option<Res<Headless>>,
+) {
+    if *source == InputSource::Replay {
+        *source = InputSource::Player;
+        if let Some((gravity, timestep)) = playback.and_then(|playback| playback.player_physics) {
+            configuration.gravity = gravity;
+            integration_parameters.dt = timestep;
+        }
+        commands.remove_resource::<ReplayPlayback>();
+        return;
+    }
+    let replayable = replayable_level(&level.id, &level_assets, &levels).is_some();
+    if headless.is_some() || *source != InputSource::Player || !replayable {
+        recording.frames.clear();
+        return;
+    }
+    let replay = Replay {
+        level_id: level.id.clone(),
+        level_version: level.version,
+        seed: run.seed,
+        timestep: integration_parameters.dt,
+        gravity: [configuration.gravity.x, configuration.gravity.y],
+        frames: std::mem::take(&mut recording.frames),
+    };
+    let result = ron::ser::to_string(&replay)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| write_data(REPLAY_FILE, &content));
+    if let Err(error) = result {
+        warn!("Failed to save replay of level {}: {}", level.id, error);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::actions::KeyBindings;
+use crate::progress::{record_key, Progress, Record};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct SavePlugin;
+
+/// Bump this when the save format changes and teach [`migrate`] how to read the old one
+const SAVE_VERSION: u32 = 3;
+const SAVE_FILE: &str = "save.ron";
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(SystemSet::on_enter(GameState::Loading).with_system(load_save.system()))
+            .add_system(write_save.system());
+    }
+}
+
+/// Everything that survives a restart of the game
+#[derive(Default, Deserialize, Serialize)]
+struct SaveData {
+    version: u32,
+    #[serde(default)]
+    progress: Progress,
+    #[serde(default)]
+    settings: Settings,
+    /// Added in version 2
+    #[serde(default)]
+    key_bindings: KeyBindings,
+}
+
+/// Only used to find out which version a save was written with
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+fn load_save(mut commands: Commands) {
+    let save = load(read_data(SAVE_FILE));
+    commands.insert_resource(save.progress);
+    commands.insert_resource(save.settings);
+    commands.insert_resource(save.key_bindings);
+}
+
+/// Start with default data if there is no save or it can not be used
+fn load(content: anyhow::Result<Option<String>>) -> SaveData {
+    match content {
+        Ok(Some(content)) => parse(&content).unwrap_or_else(|error| {
+            warn!("Resetting save data: {}", error);
+            SaveData::default()
+        }),
+        Ok(None) => SaveData::default(),
+        Err(error) => {
+            warn!("Failed to read save data: {}", error);
+            SaveData::default()
+        }
+    }
+}
+
+fn parse(content: &str) -> anyhow::Result<SaveData> {
+    let header: SaveHeader = ron::de::from_str(content)?;
+    if header.version > SAVE_VERSION {
+        anyhow::bail!(
+            "save version {} is newer than the supported version {}",
+            header.version,
+            SAVE_VERSION
+        );
+    }
+    let save = ron::de::from_str(content)?;
+
+    Ok(migrate(header.version, save))
+}
+
+/// Bring data of an older save up to date
+///
+/// Fields added in later versions fall back to their defaults while deserializing.
+fn migrate(version: u32, mut save: SaveData) -> SaveData {
+    if version < SAVE_VERSION {
+        warn!(
+            "Migrating save data from version {} to {}",
+            version, SAVE_VERSION
+        );
+    }
+    if version < 3 {
+        // best times were kept per level id, while all levels were still in their first version
+        for (id, level) in save.progress.levels.iter_mut() {
+            if let Some(time) = level.best_time.take() {
+                save.progress.records.insert(
+                    record_key(id, 1),
+                    Record {
+                        time,
+                        splits: vec![],
+                    },
+                );
+            }
+        }
+    }
+    save.version = SAVE_VERSION;
+    save
+}
+
+fn write_save(
+    state: Res<State<GameState>>,
+    progress: Res<Progress>,
+    settings: Res<Settings>,
+    key_bindings: Res<KeyBindings>,
+) {
+    let changed = progress.is_changed() || settings.is_changed() || key_bindings.is_changed();
+    if *state.current() == GameState::Loading || !changed {
+        return;
+    }
+    let save = SaveData {
+        version: SAVE_VERSION,
+        progress: progress.clone(),
+        settings: settings.clone(),
+        key_bindings: key_bindings.clone(),
+    };
+    let result = ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default())
+        .map_err(anyhow::Error::from)
+        .and_then(|content| write_data(SAVE_FILE, &content));
+    if let Err(error) = result {
+        warn!("Failed to write save data: {}", error);
+    }
+}
+
+/// Read a file of persistent game data. A missing file is not an error.
+///
+/// `name` may contain directories separated by `/`.
+pub fn read_data(name: &str) -> anyhow::Result<Option<String>> {
+    storage::read(name)
+}
+
+pub fn write_data(name: &str, content: &str) -> anyhow::Result<()> {
+    storage::write(name, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::GameControl;
+    use crate::progress::LevelProgress;
+
+    #[test]
+    fn current_save_is_read_unchanged() {
+        let mut save = SaveData {
+            version: SAVE_VERSION,
+            ..Default::default()
+        };
+        save.progress.levels.insert(
+            "tutorial".to_string(),
+            LevelProgress {
+                completed: true,
+                falls: 3,
+                best_time: None,
+            },
+        );
+        save.progress.records.insert(
+            record_key("tutorial", 2),
+            Record {
+                time: 12.5,
+                splits: vec![6., 12.5],
+            },
+        );
+        save.settings.music = false;
+        save.key_bindings.add(GameControl::Jump, KeyCode::W);
+        let content = ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default()).unwrap();
+
+        let loaded = parse(&content).unwrap();
+
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert!(loaded.progress.levels["tutorial"].completed);
+        assert_eq!(loaded.progress.levels["tutorial"].falls, 3);
+        assert_eq!(
+            loaded.progress.records[&record_key("tutorial", 2)].splits,
+            vec![6., 12.5]
+        );
+        assert!(!loaded.settings.music);
+        assert_eq!(
+            loaded.key_bindings.keys(GameControl::Jump),
+            [KeyCode::Space, KeyCode::W]
+        );
+    }
+
+    #[test]
+    fn older_save_is_migrated() {
+        let content = r#"(
+            version: 1,
+            progress: (
+                levels: {
+                    "tutorial": (completed: true, falls: 2, best_time: Some(12.5)),
+                },
+            ),
+            settings: (music: false),
+        )"#;
+
+        let loaded = parse(content).unwrap();
+
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert!(loaded.progress.levels["tutorial"].completed);
+        assert_eq!(loaded.progress.levels["tutorial"].best_time, None);
+        assert_eq!(
+            loaded.progress.records[&record_key("tutorial", 1)].time,
+            12.5
+        );
+        assert!(!loaded.settings.music);
+        assert_eq!(
+            loaded.key_bindings.keys(GameControl::Jump),
+            KeyBindings::default().keys(GameControl::Jump)
+        );
+    }
+
+    #[test]
+    fn newer_save_is_rejected() {
+        let content = format!("(version: {})", SAVE_VERSION + 1);
+
+        assert!(parse(&content).is_err());
+    }
+
+    #[test]
+    fn corrupt_save_falls_back_to_defaults() {
+        let loaded = load(Ok(Some("(version: 3, progress: (levels: {".to_string())));
+
+        assert!(loaded.progress.levels.is_empty());
+        assert!(loaded.settings.music);
+    }
+
+    #[test]
+    fn unreadable_save_falls_back_to_defaults() {
+        let loaded = load(Err(anyhow::anyhow!("permission denied")));
+
+        assert!(loaded.progress.records.is_empty());
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use std::path::PathBuf;
+    use std::{env, fs, io};
+
+    const DIRECTORY: &str = "me_and_my_unicycle";
+
+    pub fn read(name: &str) -> anyhow::Result<Option<String>> {
+        match fs::read_to_string(path(name)?) {
+            Ok(content) => Ok(Some(content)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn write(name: &str, content: &str) -> anyhow::Result<()> {
+        let path = path(name)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn path(name: &str) -> anyhow::Result<PathBuf> {
+        Ok(data_directory()?.join(DIRECTORY).join(name))
+    }
+
+    #[cfg(target_os = "windows")]
+    fn data_directory() -> anyhow::Result<PathBuf> {
+        Ok(PathBuf::from(env::var("APPDATA")?))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn data_directory() -> anyhow::Result<PathBuf> {
+        Ok(PathBuf::from(env::var("HOME")?).join("Library/Application Support"))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    fn data_directory() -> anyhow::Result<PathBuf> {
+        match env::var("XDG_DATA_HOME") {
+            Ok(directory) if !directory.is_empty() => Ok(PathBuf::from(directory)),
+            _ => Ok(PathBuf::from(env::var("HOME")?).join(".local/share")),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use anyhow::anyhow;
+    use web_sys::Storage;
+
+    const PREFIX: &str = "me_and_my_unicycle/";
+
+    pub fn read(name: &str) -> anyhow::Result<Option<String>> {
+        local_storage()?
+            .get_item(&format!("{}{}", PREFIX, name))
+            .map_err(|_| anyhow!("failed to read from localStorage"))
+    }
+
+    pub fn write(name: &str, content: &str) -> anyhow::Result<()> {
+        local_storage()?
+            .set_item(&format!("{}{}", PREFIX, name), content)
+            .map_err(|_| anyhow!("failed to write to localStorage"))
+    }
+
+    fn local_storage() -> anyhow::Result<Storage> {
+        web_sys::window()
+            .ok_or_else(|| anyhow!("no window"))?
+            .local_storage()
+            .ok()
+            .flatten()
+            .ok_or_else(|| anyhow!("localStorage is not available"))
+    }
+}
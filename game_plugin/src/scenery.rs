@@ -0,0 +1,267 @@
+use crate::levels::Level;
+use crate::loading::TextureAssets;
+use crate::player::PHYSICS_SCALE;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::render::mesh::{shape, Indices, VertexAttributeValues};
+use bevy::render::pipeline::PrimitiveTopology;
+use bevy::render::texture::AddressMode;
+use bevy::utils::HashMap;
+use bevy_rapier2d::prelude::*;
+
+pub struct SceneryPlugin;
+
+/// Width in pixels of one tile of the ground and platform textures
+const TILE_SIZE: f32 = 64.;
+/// Width in pixels of the caps rounding off both ends of a ground strip
+const EDGE_WIDTH: f32 = 32.;
+/// In front of the background layers, but behind the rider
+const SCENERY_DEPTH: f32 = -0.01;
+/// Pixels the ground of polyline and heightfield terrain reaches below its lowest point
+const TERRAIN_DEPTH: f32 = 64.;
+
+impl Plugin for SceneryPlugin {
+    /// Starting the game with `--debug-render` draws the colliders on top of the scenery. F3 toggles it.
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(DebugRender {
+            enabled: std::env::args().any(|arg| arg == "--debug-render"),
+        })
+        .init_resource::<SceneryVisuals>()
+        .add_system_set(
+            SystemSet::on_exit(GameState::Loading).with_system(repeat_textures.system()),
+        )
+        .add_system(toggle_debug_render.system())
+        .add_system(show_debug_render.system())
+        // colliders spawned or despawned during the update are dressed or undressed right away
+        .add_system_to_stage(CoreStage::PostUpdate, dress_scenery.system())
+        .add_system_to_stage(CoreStage::PostUpdate, undress_scenery.system());
+    }
+}
+
+/// Level colliders that are drawn with textures
+pub enum Scenery {
+    Ground,
+    Wall,
+    /// Obstacles of the level. Balls are drawn as boulders.
+    Platform,
+}
+
+/// Developer toggle to draw the colliders of the level as they are seen by the physics
+pub struct DebugRender {
+    pub enabled: bool,
+}
+
+/// Sprites drawing each scenery collider
+#[derive(Default)]
+struct SceneryVisuals(HashMap<Entity, Vec<Entity>>);
+
+/// The ground and platform textures are repeated along their colliders
+fn repeat_textures(
+    texture_assets: Option<Res<TextureAssets>>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let texture_assets = match texture_assets {
+        Some(texture_assets) => texture_assets,
+        None => return,
+    };
+    for handle in [&texture_assets.ground, &texture_assets.platform].iter() {
+        if let Some(texture) = textures.get_mut(*handle) {
+            texture.sampler.address_mode_u = AddressMode::Repeat;
+            texture.sampler.address_mode_v = AddressMode::Repeat;
+        }
+    }
+}
+
+fn toggle_debug_render(input: Res<Input<KeyCode>>, mut debug_render: ResMut<DebugRender>) {
+    if input.just_pressed(KeyCode::F3) {
+        debug_render.enabled = !debug_render.enabled;
+    }
+}
+
+fn show_debug_render(
+    debug_render: Res<DebugRender>,
+    mut debug_query: Query<&mut Visible, (With<ColliderDebugRender>, With<Scenery>)>,
+) {
+    for mut visible in debug_query.iter_mut() {
+        if visible.is_visible != debug_render.enabled {
+            visible.is_visible = debug_render.enabled;
+        }
+    }
+}
+
+/// A unit quad repeating its texture the given number of times in both directions
+fn tiled_quad(repeat: Vec2) -> Mesh {
+    let mut mesh = Mesh::from(shape::Quad::new(Vec2::ONE));
+    if let Some(VertexAttributeValues::Float2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        for uv in uvs.iter_mut() {
+            uv[0] *= repeat.x;
+            uv[1] *= repeat.y;
+        }
+    }
+    mesh
+}
+
+/// The ground below the given surface segments in pixels, textured in world space
+fn terrain_mesh(segments: Vec<[Vec2; 2]>) -> Mesh {
+    let bottom = segments
+        .iter()
+        .flat_map(|segment| segment.iter())
+        .fold(f32::INFINITY, |bottom, point| bottom.min(point.y))
+        - TERRAIN_DEPTH;
+    let mut positions = vec![];
+    let mut indices = vec![];
+    for [start, end] in segments {
+        let first = positions.len() as u32;
+        positions.extend_from_slice(&[
+            [start.x, start.y, 0.],
+            [start.x, bottom, 0.],
+            [end.x, bottom, 0.],
+            [end.x, end.y, 0.],
+        ]);
+        indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+    let normals = vec![[0., 0., 1.]; positions.len()];
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|position| [position[0] / TILE_SIZE, -position[1] / TILE_SIZE])
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    // the quads are wound either way, depending on the direction of their segment
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Level and textures are missing until loading is done
+fn dress_scenery(
+    mut commands: Commands,
+    level: Option<Res<Level>>,
+    textures: Option<Res<TextureAssets>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut visuals: ResMut<SceneryVisuals>,
+    scenery_query: Query<(Entity, &Scenery, &ColliderShape, &ColliderPosition), Added<Scenery>>,
+) {
+    let (theme, textures) = match (level, textures) {
+        (Some(level), Some(textures)) => (level.theme, textures),
+        _ => return,
+    };
+    for (entity, scenery, shape, position) in scenery_query.iter() {
+        let translation = position.translation.vector * PHYSICS_SCALE;
+        let mut transform =
+            Transform::from_translation(Vec3::new(translation.x, translation.y, SCENERY_DEPTH));
+        transform.rotation = Quat::from_rotation_z(position.rotation.angle());
+        let (texture, color) = match scenery {
+            Scenery::Ground => (textures.ground.clone(), theme.ground_color()),
+            Scenery::Wall => (textures.platform.clone(), theme.ground_color()),
+            Scenery::Platform => (textures.platform.clone(), theme.platform_color()),
+        };
+        let mut sprites = vec![];
+        if let Some(cuboid) = shape.as_cuboid() {
+            let size = Vec2::new(cuboid.half_extents.x, cuboid.half_extents.y) * 2. * PHYSICS_SCALE;
+            // the caps take the place of the strip's ends
+            let strip = match scenery {
+                Scenery::Ground => Vec2::new((size.x - 2. * EDGE_WIDTH).max(0.), size.y),
+                _ => size,
+            };
+            sprites.push(
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite::new(strip),
+                        mesh: meshes.add(tiled_quad(strip / TILE_SIZE)),
+                        material: materials.add(ColorMaterial::modulated_texture(texture, color)),
+                        transform,
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+            if let Scenery::Ground = scenery {
+                let edge_material = materials.add(ColorMaterial::modulated_texture(
+                    textures.ground_edge.clone(),
+                    color,
+                ));
+                for side in [-1., 1.].iter() {
+                    let x = translation.x + side * 0.5 * (size.x - EDGE_WIDTH);
+                    let mut sprite = Sprite::new(Vec2::new(EDGE_WIDTH, size.y));
+                    // the cap texture ends a strip on its right side
+                    sprite.flip_x = *side < 0.;
+                    sprites.push(
+                        commands
+                            .spawn_bundle(SpriteBundle {
+                                sprite,
+                                material: edge_material.clone(),
+                                transform: Transform::from_translation(Vec3::new(
+                                    x,
+                                    translation.y,
+                                    SCENERY_DEPTH + 0.001,
+                                )),
+                                ..Default::default()
+                            })
+                            .id(),
+                    );
+                }
+            }
+        } else if let Some(segments) = shape
+            .as_polyline()
+            .map(|polyline| polyline.segments().collect::<Vec<_>>())
+            .or_else(|| {
+                shape
+                    .as_heightfield()
+                    .map(|heightfield| heightfield.segments().collect())
+            })
+        {
+            let segments = segments
+                .iter()
+                .map(|segment| {
+                    [
+                        Vec2::new(segment.a.x, segment.a.y) * PHYSICS_SCALE,
+                        Vec2::new(segment.b.x, segment.b.y) * PHYSICS_SCALE,
+                    ]
+                })
+                .collect();
+            sprites.push(
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        // the mesh is already in pixels
+                        sprite: Sprite::new(Vec2::ONE),
+                        mesh: meshes.add(terrain_mesh(segments)),
+                        material: materials.add(ColorMaterial::modulated_texture(texture, color)),
+                        transform,
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+        } else if let Some(ball) = shape.as_ball() {
+            let diameter = 2. * ball.radius * PHYSICS_SCALE;
+            sprites.push(
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite::new(Vec2::new(diameter, diameter)),
+                        material: materials.add(ColorMaterial::modulated_texture(
+                            textures.boulder.clone(),
+                            color,
+                        )),
+                        transform,
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+        }
+        visuals.0.insert(entity, sprites);
+    }
+}
+
+/// Remove the sprites of despawned colliders, e.g. when leaving a level or passing a chunk
+fn undress_scenery(
+    mut commands: Commands,
+    mut visuals: ResMut<SceneryVisuals>,
+    removed: RemovedComponents<Scenery>,
+) {
+    for entity in removed.iter() {
+        for sprite in visuals.0.remove(&entity).unwrap_or_default() {
+            commands.entity(sprite).despawn();
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::loading::FontAssets;
+use crate::lost::ButtonMaterials;
+use crate::menu::{leave_sub_menu, spawn_menu, ButtonActivated, SubMenuOrigin};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Settings>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Settings).with_system(show_settings.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(change_settings.system())
+                    .with_system(update_labels.system()),
+            );
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub music: bool,
+    pub sound_effects: bool,
+    /// Show time, distance, speed and lean while riding
+    pub show_hud: bool,
+    /// Shake the camera on hard landings, crashes and falls
+    pub screen_shake: bool,
+    /// Stick deflection below this is ignored
+    pub stick_deadzone: f32,
+    /// Exponent applied to stick deflection. Higher values give finer control around the center.
+    pub stick_response: f32,
+}
+
+const STICK_DEADZONES: [f32; 5] = [0.05, 0.1, 0.15, 0.2, 0.3];
+const STICK_RESPONSES: [f32; 4] = [1., 1.5, 2., 3.];
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            music: true,
+            sound_effects: true,
+            show_hud: true,
+            screen_shake: true,
+            stick_deadzone: 0.15,
+            stick_response: 1.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SettingsButton {
+    Music,
+    SoundEffects,
+    Hud,
+    ScreenShake,
+    StickDeadzone,
+    StickResponse,
+    Controls,
+    Back,
+}
+
+impl SettingsButton {
+    fn label(&self, settings: &Settings) -> String {
+        match self {
+            SettingsButton::Music => format!("Music: {}", on_off(settings.music)),
+            SettingsButton::SoundEffects => format!("Sounds: {}", on_off(settings.sound_effects)),
+            SettingsButton::Hud => format!("HUD: {}", on_off(settings.show_hud)),
+            SettingsButton::ScreenShake => {
+                format!("Screen shake: {}", on_off(settings.screen_shake))
+            }
+            SettingsButton::StickDeadzone => {
+                format!("Stick deadzone: {:.0}%", settings.stick_deadzone * 100.)
+            }
+            SettingsButton::StickResponse => format!("Stick curve: {:.1}", settings.stick_response),
+            SettingsButton::Controls => "Controls".to_string(),
+            SettingsButton::Back => "Back".to_string(),
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// The option following `current`. Unknown values start over at the first option.
+fn cycle(options: &[f32], current: f32) -> f32 {
+    let index = options
+        .iter()
+        .position(|option| (option - current).abs() < f32::EPSILON)
+        .map(|index| (index + 1) % options.len())
+        .unwrap_or(0);
+    options[index]
+}
+
+fn show_settings(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    settings: Res<Settings>,
+) {
+    let buttons = [
+        SettingsButton::Music,
+        SettingsButton::SoundEffects,
+        SettingsButton::Hud,
+        SettingsButton::ScreenShake,
+        SettingsButton::StickDeadzone,
+        SettingsButton::StickResponse,
+        SettingsButton::Controls,
+        SettingsButton::Back,
+    ];
+    spawn_menu(
+        &mut commands,
+        &font_assets,
+        &button_materials,
+        "Settings",
+        buttons
+            .iter()
+            .map(|button| (button.label(&settings), *button))
+            .collect(),
+    );
+}
+
+fn change_settings(
+    mut activated: EventReader<ButtonActivated>,
+    buttons: Query<&SettingsButton>,
+    mut settings: ResMut<Settings>,
+    mut state: ResMut<State<GameState>>,
+    mut origin: ResMut<SubMenuOrigin>,
+) {
+    for ButtonActivated(entity) in activated.iter() {
+        match buttons.get(*entity) {
+            Ok(SettingsButton::Music) => settings.music = !settings.music,
+            Ok(SettingsButton::SoundEffects) => settings.sound_effects = !settings.sound_effects,
+            Ok(SettingsButton::Hud) => settings.show_hud = !settings.show_hud,
+            Ok(SettingsButton::ScreenShake) => settings.screen_shake = !settings.screen_shake,
+            Ok(SettingsButton::StickDeadzone) => {
+                settings.stick_deadzone = cycle(&STICK_DEADZONES, settings.stick_deadzone)
+            }
+            Ok(SettingsButton::StickResponse) => {
+                settings.stick_response = cycle(&STICK_RESPONSES, settings.stick_response)
+            }
+            Ok(SettingsButton::Controls) => state.set(GameState::Controls).unwrap(),
+            Ok(SettingsButton::Back) => leave_sub_menu(&mut state, &mut origin),
+            Err(_) => (),
+        }
+    }
+}
+
+fn update_labels(
+    settings: Res<Settings>,
+    buttons: Query<(&SettingsButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (button, children) in buttons.iter() {
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            text.sections[0].value = button.label(&settings);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::actions::{Actions, ActionsSystem, InputSource};
+use crate::levels::{to_physics, ForLevel, Level};
+use crate::loading::FontAssets;
+use crate::lost::ButtonMaterials;
+use crate::physics::{riding, GameStage};
+use crate::player::{Body, PHYSICS_SCALE};
+use crate::progress::{Progress, Record};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_rapier2d::physics::PhysicsSystems;
+use bevy_rapier2d::prelude::*;
+
+pub struct SpeedrunPlugin;
+
+/// Number of equally long parts a level is split into. The last split is the finish line.
+const SPLIT_COUNT: usize = 4;
+
+impl Plugin for SpeedrunPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LevelTimer>()
+            .add_event::<NewRecord>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::PrepareLevel).with_system(reset_timer.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::InLevel).with_system(spawn_split_text.system()),
+            )
+            .add_system_set_to_stage(
+                GameStage::PhysicsStep,
+                SystemSet::new()
+                    .with_run_criteria(riding.system())
+                    .after(ActionsSystem::Replay)
+                    .before(PhysicsSystems::StepWorld)
+                    .with_system(tick_timer.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel).with_system(check_splits.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Finished).with_system(finish_run.system()),
+            );
+    }
+}
+
+/// Time of the current attempt of a level
+///
+/// The timer starts with the first input and keeps the time at every passed split point.
+#[derive(Default)]
+pub struct LevelTimer {
+    time: f64,
+    started: bool,
+    splits: Vec<f32>,
+}
+
+impl LevelTimer {
+    pub fn seconds(&self) -> f32 {
+        self.time as f32
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started
+    }
+
+    fn reset(&mut self) {
+        *self = LevelTimer::default();
+    }
+}
+
+/// Sent when a finished run beats the personal best of the level
+pub struct NewRecord;
+
+struct SplitText;
+
+/// Positions of all split points in physics units
+fn split_points(level: &Level) -> Vec<f32> {
+    let start = to_physics(level.start.body)[0];
+    let finish = level.finish_line / PHYSICS_SCALE;
+    (1..=SPLIT_COUNT)
+        .map(|split| start + (finish - start) * split as f32 / SPLIT_COUNT as f32)
+        .collect()
+}
+
+fn reset_timer(mut timer: ResMut<LevelTimer>) {
+    timer.reset();
+}
+
+/// The timer counts simulated time, so it matches a replay of the same run
+fn tick_timer(
+    integration_parameters: Res<IntegrationParameters>,
+    actions: Res<Actions>,
+    mut timer: ResMut<LevelTimer>,
+) {
+    if actions.restart {
+        timer.reset();
+        return;
+    }
+    let moving = actions.jump
+        || actions.paddling.unwrap_or(0.) != 0.
+        || actions.head_balance.unwrap_or(0.) != 0.;
+    if moving {
+        timer.started = true;
+    }
+    if timer.started {
+        timer.time += integration_parameters.dt as f64;
+    }
+}
+
+fn check_splits(
+    level: Res<Level>,
+    progress: Res<Progress>,
+    mut timer: ResMut<LevelTimer>,
+    body_query: Query<&RigidBodyPosition, With<Body>>,
+    mut text_query: Query<&mut Text, With<SplitText>>,
+) {
+    if level.endless {
+        return;
+    }
+    if let Ok(body) = body_query.single() {
+        let points = split_points(&level);
+        let passed = timer.splits.len();
+        // the finish line is handled when entering `GameState::Finished`
+        if passed < SPLIT_COUNT - 1 && body.position.translation.x >= points[passed] {
+            let time = timer.seconds();
+            timer.splits.push(time);
+        }
+    }
+    let value = match timer.splits.last() {
+        Some(time) => split_comparison(
+            timer.splits.len(),
+            *time,
+            progress
+                .record(&level)
+                .and_then(|record| record.splits.get(timer.splits.len() - 1)),
+        ),
+        None => String::new(),
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn split_comparison(split: usize, time: f32, best: Option<&f32>) -> String {
+    match best {
+        Some(best) => format!("Split {}: {:.2} s ({:+.2})", split, time, time - best),
+        None => format!("Split {}: {:.2} s", split, time),
+    }
+}
+
+fn spawn_split_text(mut commands: Commands, level: Res<Level>, font_assets: Res<FontAssets>) {
+    if level.endless {
+        return;
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 25.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(SplitText)
+        .insert(ForLevel);
+}
+
+/// Stop the timer, keep the run if it is a new record and show how it compares
+fn finish_run(
+    mut commands: Commands,
+    level: Res<Level>,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    mut timer: ResMut<LevelTimer>,
+    mut progress: ResMut<Progress>,
+    mut new_records: EventWriter<NewRecord>,
+    source: Res<InputSource>,
+) {
+    if level.endless || level.is_custom() || *source != InputSource::Player {
+        return;
+    }
+    let time = timer.seconds();
+    timer.splits.push(time);
+    let previous = progress.record(&level).map(|record| record.time);
+    let run = Record {
+        time,
+        splits: timer.splits.clone(),
+    };
+    let message = if progress.submit_run(&level, run) {
+        new_records.send(NewRecord);
+        format!("New record! {:.3} s", time)
+    } else {
+        format!("{:.3} s - record {:.3} s", time, previous.unwrap_or(time))
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(60.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: button_materials.none.clone(),
+            ..Default::default()
+        })
+        .insert(ForLevel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    message,
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
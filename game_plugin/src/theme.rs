@@ -0,0 +1,104 @@
+use crate::background::{BackgroundLayer, BackgroundTexture};
+use crate::levels::Level;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(ClearColor(Theme::default().clear_color()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::PrepareLevel).with_system(apply_theme.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu).with_system(reset_clear_color.system()),
+            );
+    }
+}
+
+/// The look of a level
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum Theme {
+    /// Pencil sketches on grey paper
+    #[default]
+    Sketch,
+    Dusk,
+    Night,
+}
+
+impl Theme {
+    pub fn backgrounds(&self) -> Vec<BackgroundLayer> {
+        match self {
+            Theme::Sketch => vec![BackgroundLayer {
+                textures: vec![
+                    BackgroundTexture::Background1,
+                    BackgroundTexture::Background3,
+                    BackgroundTexture::Background2,
+                ],
+                scroll: 0.6,
+                offset: 300.,
+                tile_width: 800.,
+            }],
+            Theme::Dusk => vec![
+                BackgroundLayer {
+                    textures: vec![
+                        BackgroundTexture::Background2,
+                        BackgroundTexture::Background1,
+                    ],
+                    scroll: 0.3,
+                    offset: 380.,
+                    tile_width: 800.,
+                },
+                BackgroundLayer {
+                    textures: vec![BackgroundTexture::Background3],
+                    scroll: 0.7,
+                    offset: 260.,
+                    tile_width: 1000.,
+                },
+            ],
+            Theme::Night => vec![BackgroundLayer {
+                textures: vec![
+                    BackgroundTexture::Background3,
+                    BackgroundTexture::Background2,
+                ],
+                scroll: 0.4,
+                offset: 320.,
+                tile_width: 800.,
+            }],
+        }
+    }
+
+    pub fn clear_color(&self) -> Color {
+        match self {
+            Theme::Sketch => Color::rgb(0.4, 0.4, 0.4),
+            Theme::Dusk => Color::rgb(0.45, 0.3, 0.35),
+            Theme::Night => Color::rgb(0.12, 0.14, 0.22),
+        }
+    }
+
+    pub fn ground_color(&self) -> Color {
+        match self {
+            Theme::Sketch => Color::BEIGE,
+            Theme::Dusk => Color::rgb(0.55, 0.4, 0.3),
+            Theme::Night => Color::rgb(0.3, 0.33, 0.45),
+        }
+    }
+
+    pub fn platform_color(&self) -> Color {
+        match self {
+            Theme::Sketch => Color::BEIGE,
+            Theme::Dusk => Color::rgb(0.75, 0.5, 0.35),
+            Theme::Night => Color::rgb(0.45, 0.5, 0.65),
+        }
+    }
+}
+
+fn apply_theme(level: Res<Level>, mut clear_color: ResMut<ClearColor>) {
+    clear_color.0 = level.theme.clear_color();
+}
+
+fn reset_clear_color(mut clear_color: ResMut<ClearColor>) {
+    clear_color.0 = Theme::default().clear_color();
+}
@@ -0,0 +1,205 @@
+use crate::actions::{Actions, ActionsSystem, InputSource};
+use crate::levels::ForLevel;
+use crate::loading::FontAssets;
+use crate::GameState;
+use bevy::input::touch::TouchInput;
+use bevy::prelude::*;
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TouchControls>()
+            .init_resource::<TouchMaterials>()
+            .add_system(detect_touch.system())
+            .add_system_set(
+                SystemSet::on_update(GameState::InLevel)
+                    .with_system(show_touch_overlay.system())
+                    .with_system(
+                        set_touch_actions
+                            .system()
+                            .after(ActionsSystem::SetMovementActions),
+                    ),
+            );
+    }
+}
+
+/// On-screen controls are shown as soon as the first touch is detected
+#[derive(Default)]
+pub struct TouchControls {
+    pub enabled: bool,
+}
+
+struct TouchMaterials {
+    pad: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+}
+
+impl FromWorld for TouchMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        TouchMaterials {
+            pad: materials.add(Color::rgba(0.15, 0.15, 0.15, 0.3).into()),
+            button: materials.add(Color::rgba(0.15, 0.15, 0.15, 0.5).into()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TouchControl {
+    /// Horizontal position on the pad controls the paddling direction and strength
+    PaddlePad,
+    /// Horizontal position on the pad controls the head balance
+    BalancePad,
+    Jump,
+    Restart,
+}
+
+struct TouchOverlay;
+
+fn detect_touch(mut touch_events: EventReader<TouchInput>, mut controls: ResMut<TouchControls>) {
+    if !controls.enabled && touch_events.iter().next().is_some() {
+        controls.enabled = true;
+    }
+}
+
+fn show_touch_overlay(
+    mut commands: Commands,
+    controls: Res<TouchControls>,
+    font_assets: Res<FontAssets>,
+    materials: Res<TouchMaterials>,
+    overlays: Query<Entity, With<TouchOverlay>>,
+) {
+    if !controls.enabled || overlays.iter().next().is_some() {
+        return;
+    }
+    let elements = [
+        (
+            TouchControl::PaddlePad,
+            "Paddle",
+            Rect {
+                left: Val::Px(10.),
+                bottom: Val::Px(10.),
+                ..Default::default()
+            },
+            Size::new(Val::Px(260.), Val::Px(130.)),
+        ),
+        (
+            TouchControl::BalancePad,
+            "Lean",
+            Rect {
+                right: Val::Px(10.),
+                bottom: Val::Px(10.),
+                ..Default::default()
+            },
+            Size::new(Val::Px(260.), Val::Px(130.)),
+        ),
+        (
+            TouchControl::Jump,
+            "Jump",
+            Rect {
+                right: Val::Px(10.),
+                bottom: Val::Px(150.),
+                ..Default::default()
+            },
+            Size::new(Val::Px(130.), Val::Px(80.)),
+        ),
+        (
+            TouchControl::Restart,
+            "Restart",
+            Rect {
+                left: Val::Px(10.),
+                top: Val::Px(10.),
+                ..Default::default()
+            },
+            Size::new(Val::Px(130.), Val::Px(50.)),
+        ),
+    ];
+    for (control, label, position, size) in elements.iter() {
+        let material = match control {
+            TouchControl::PaddlePad | TouchControl::BalancePad => materials.pad.clone(),
+            TouchControl::Jump | TouchControl::Restart => materials.button.clone(),
+        };
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: *position,
+                    size: *size,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                material,
+                ..Default::default()
+            })
+            .insert(*control)
+            .insert(TouchOverlay)
+            .insert(ForLevel)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        label.to_string(),
+                        TextStyle {
+                            font: font_assets.fira_sans.clone(),
+                            font_size: 30.0,
+                            color: Color::rgba(0.9, 0.9, 0.9, 0.7),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+/// Every touch on the overlay writes into [`Actions`]
+///
+/// Each touch is handled on its own, so pads and buttons can be used at the same time.
+/// Held keys and gamepad input take precedence over the pads.
+fn set_touch_actions(
+    controls: Res<TouchControls>,
+    touches: Res<Touches>,
+    windows: Res<Windows>,
+    mut actions: ResMut<Actions>,
+    elements: Query<(&TouchControl, &Node, &GlobalTransform)>,
+    source: Res<InputSource>,
+) {
+    if !controls.enabled || *source != InputSource::Player {
+        return;
+    }
+    let window_height = windows.get_primary().map(|window| window.height());
+    let mut paddling = 0.;
+    let mut head_balance = 0.;
+    for touch in touches.iter() {
+        let position = ui_position(touch.position(), window_height);
+        for (control, node, transform) in elements.iter() {
+            let offset = position - transform.translation.truncate();
+            let half_size = node.size / 2.;
+            if offset.x.abs() > half_size.x || offset.y.abs() > half_size.y {
+                continue;
+            }
+            let pad_value = (offset.x / half_size.x).clamp(-1., 1.);
+            match control {
+                TouchControl::PaddlePad => paddling = pad_value,
+                TouchControl::BalancePad => head_balance = pad_value,
+                TouchControl::Jump => actions.jump |= touches.just_pressed(touch.id()),
+                TouchControl::Restart => actions.restart |= touches.just_pressed(touch.id()),
+            }
+        }
+    }
+    if paddling != 0. && actions.paddling.unwrap_or(0.) == 0. {
+        actions.paddling = Some(paddling);
+    }
+    if head_balance != 0. && actions.head_balance.unwrap_or(0.) == 0. {
+        actions.head_balance = Some(head_balance);
+    }
+}
+
+/// Touch positions on the web start at the top of the window, while UI nodes are placed from the bottom
+fn ui_position(position: Vec2, window_height: Option<f32>) -> Vec2 {
+    match window_height {
+        Some(height) if cfg!(target_arch = "wasm32") => Vec2::new(position.x, height - position.y),
+        _ => position,
+    }
+}
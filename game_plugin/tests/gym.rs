@@ -0,0 +1,67 @@
+use game_plugin::{serve, Gym, GymAction, Step};
+use std::io::Cursor;
+
+/// Upper bound of steps for an episode
+const MAX_STEPS: usize = 60 * 60;
+
+fn gym() -> Gym {
+    Gym::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets")).unwrap()
+}
+
+#[test]
+fn paddling_forward_ends_the_episode() {
+    let mut gym = gym();
+    let observation = gym.reset("tutorial", 0).unwrap();
+    assert!(observation.terrain.iter().all(Option::is_some));
+    let action = GymAction {
+        paddling: 1.,
+        ..Default::default()
+    };
+    let mut total_reward = 0.;
+    for _ in 0..MAX_STEPS {
+        let step = gym.step(&action).unwrap();
+        total_reward += step.reward;
+        if step.done {
+            assert!(total_reward < 0.);
+            assert!(gym.step(&action).is_err());
+            gym.reset("tutorial", 0).unwrap();
+            return;
+        }
+    }
+    panic!("The episode did not end");
+}
+
+#[test]
+fn endless_mode_is_seeded() {
+    let mut gym = gym();
+    let run = |gym: &mut Gym, seed| {
+        gym.reset("endless", seed).unwrap();
+        (0..120)
+            .map(|_| gym.step(&GymAction::default()).unwrap().observation.terrain)
+            .last()
+            .unwrap()
+    };
+    let first = run(&mut gym, 7);
+    assert_eq!(first, run(&mut gym, 7));
+}
+
+#[test]
+fn serves_json_lines() {
+    let mut gym = gym();
+    let input = concat!(
+        r#"{"reset":{"level":"first"}}"#,
+        "\n",
+        r#"{"step":{"paddling":0.5,"head_balance":0,"jump":false}}"#,
+        "\n",
+        r#"{"reset":{"level":"nowhere"}}"#,
+        "\n",
+    );
+    let mut output = Vec::new();
+    serve(&mut gym, Cursor::new(input), &mut output).unwrap();
+    let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].contains("\"terrain\""));
+    let step: Step = serde_json::from_str(lines[1]).unwrap();
+    assert!(!step.done);
+    assert!(lines[2].starts_with("{\"error\":"));
+}
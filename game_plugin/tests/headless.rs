@@ -0,0 +1,237 @@
+use bevy::app::App;
+use bevy::asset::AssetServerSettings;
+use bevy::prelude::*;
+use bevy::render::camera::Camera;
+use bevy::render::render_graph::base::camera::CAMERA_2D;
+use bevy_rapier2d::prelude::*;
+use game_plugin::{
+    Actions, GameState, HeadlessGamePlugin, InputSource, Level, LevelAssets, Terrain,
+};
+
+/// Upper bound of frames for anything the tests wait for
+const MAX_FRAMES: usize = 60 * 60;
+
+fn headless_app() -> App {
+    let mut builder = App::build();
+    builder
+        .insert_resource(AssetServerSettings {
+            asset_folder: concat!(env!("CARGO_MANIFEST_DIR"), "/../assets").to_string(),
+        })
+        .add_plugins(MinimalPlugins)
+        .add_plugin(HeadlessGamePlugin);
+    builder.app
+}
+
+fn state(app: &App) -> GameState {
+    app.world
+        .get_resource::<State<GameState>>()
+        .unwrap()
+        .current()
+        .clone()
+}
+
+/// Update the app until it reaches the given state
+fn run_until(app: &mut App, target: GameState, mut script: impl FnMut(&mut Actions)) {
+    for _ in 0..MAX_FRAMES {
+        if state(app) == target {
+            return;
+        }
+        script(&mut app.world.get_resource_mut::<Actions>().unwrap());
+        app.update();
+        if state(app) == GameState::Loading {
+            // levels are loaded on another thread
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+    panic!("Expected {:?}, but the game is in {:?}", target, state(app));
+}
+
+fn level(app: &App, handle: &Handle<Level>) -> Level {
+    let levels = app.world.get_resource::<Assets<Level>>().unwrap();
+    levels.get(handle).unwrap().clone()
+}
+
+fn start_tutorial(app: &mut App) {
+    run_until(app, GameState::Menu, |_| ());
+    let tutorial = level(
+        app,
+        &app.world.get_resource::<LevelAssets>().unwrap().tutorial,
+    );
+    start_level(app, tutorial, InputSource::External);
+}
+
+/// Play the level from the title screen
+fn start_level(app: &mut App, level: Level, source: InputSource) {
+    app.world.insert_resource(level);
+    app.world.insert_resource(source);
+    app.world
+        .get_resource_mut::<State<GameState>>()
+        .unwrap()
+        .set(GameState::Prepare)
+        .unwrap();
+    run_until(app, GameState::InLevel, |_| ());
+}
+
+#[test]
+fn loads_levels_without_renderer() {
+    let mut app = headless_app();
+    run_until(&mut app, GameState::Menu, |_| ());
+    let level_assets = app.world.get_resource::<LevelAssets>().unwrap();
+    let levels = app.world.get_resource::<Assets<Level>>().unwrap();
+    assert_eq!(levels.get(&level_assets.tutorial).unwrap().id, "tutorial");
+}
+
+#[test]
+fn paddling_forward_on_tutorial_falls() {
+    let mut app = headless_app();
+    start_tutorial(&mut app);
+    run_until(&mut app, GameState::Lost, |actions| {
+        actions.paddling = Some(1.);
+    });
+}
+
+#[test]
+fn pausing_freezes_physics() {
+    let mut app = headless_app();
+    start_tutorial(&mut app);
+    let physics_active = |app: &App| {
+        app.world
+            .get_resource::<RapierConfiguration>()
+            .unwrap()
+            .physics_pipeline_active
+    };
+    let mut game_state = app.world.get_resource_mut::<State<GameState>>().unwrap();
+    game_state.push(GameState::Paused).unwrap();
+    app.update();
+    assert_eq!(state(&app), GameState::Paused);
+    assert!(!physics_active(&app));
+
+    let mut game_state = app.world.get_resource_mut::<State<GameState>>().unwrap();
+    game_state.pop().unwrap();
+    app.update();
+    assert_eq!(state(&app), GameState::InLevel);
+    assert!(physics_active(&app));
+}
+
+#[test]
+fn idle_rider_keeps_balance() {
+    let mut app = headless_app();
+    start_tutorial(&mut app);
+    for _ in 0..5 * 60 {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::InLevel);
+}
+
+#[test]
+fn riding_in_a_valley_is_no_fall() {
+    let mut app = headless_app();
+    run_until(&mut app, GameState::Menu, |_| ());
+    let mut level = level(
+        &app,
+        &app.world.get_resource::<LevelAssets>().unwrap().tutorial,
+    );
+    // replace the ground around the start with a valley below it
+    level.holes.insert(0, [-200., 200.]);
+    level.terrain.push(Terrain::Polyline(vec![
+        [-200., 32.],
+        [-100., -64.],
+        [100., -64.],
+        [200., 32.],
+    ]));
+    for start in [
+        &mut level.start.wheel,
+        &mut level.start.body,
+        &mut level.start.head,
+    ]
+    .iter_mut()
+    {
+        start[1] -= 96.;
+    }
+    start_level(&mut app, level, InputSource::External);
+    for _ in 0..5 * 60 {
+        app.update();
+    }
+    assert_eq!(state(&app), GameState::InLevel);
+}
+
+#[test]
+fn autopilot_clears_every_level() {
+    let mut app = headless_app();
+    run_until(&mut app, GameState::Menu, |_| ());
+    let handles = app.world.get_resource::<LevelAssets>().unwrap().all();
+    for handle in handles.iter() {
+        let level = level(&app, handle);
+        let id = level.id.clone();
+        start_level(&mut app, level, InputSource::Autopilot);
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            if state(&app) != GameState::InLevel {
+                break;
+            }
+        }
+        assert_eq!(state(&app), GameState::Finished, "level {}", id);
+        app.world
+            .get_resource_mut::<State<GameState>>()
+            .unwrap()
+            .replace(GameState::Menu)
+            .unwrap();
+        app.update();
+    }
+}
+
+#[test]
+fn autopilot_rides_over_terrain() {
+    let mut app = headless_app();
+    run_until(&mut app, GameState::Menu, |_| ());
+    let mut level = level(
+        &app,
+        &app.world.get_resource::<LevelAssets>().unwrap().tutorial,
+    );
+    // fill up the hole and put a step too high to roll up behind it
+    let hole = level.holes[0];
+    level
+        .terrain
+        .push(Terrain::Polyline(vec![[hole[0], 32.], [hole[1], 32.]]));
+    level.terrain.push(Terrain::Polyline(vec![
+        [2000., 32.],
+        [2000., 80.],
+        [2100., 80.],
+        [2150., 32.],
+    ]));
+    start_level(&mut app, level, InputSource::Autopilot);
+    for _ in 0..MAX_FRAMES {
+        app.update();
+        if state(&app) != GameState::InLevel {
+            break;
+        }
+    }
+    assert_eq!(state(&app), GameState::Finished);
+}
+
+#[test]
+fn camera_follows_rider_inside_level() {
+    let mut app = headless_app();
+    run_until(&mut app, GameState::Menu, |_| ());
+    let tutorial = level(
+        &app,
+        &app.world.get_resource::<LevelAssets>().unwrap().tutorial,
+    );
+    let ground = tutorial.ground;
+    start_level(&mut app, tutorial, InputSource::Autopilot);
+    let mut camera_query = app.world.query::<(&Camera, &Transform)>();
+    let mut camera_position = |app: &mut App| {
+        camera_query
+            .iter(&app.world)
+            .find(|(camera, _)| camera.name.as_deref() == Some(CAMERA_2D))
+            .unwrap()
+            .1
+            .translation
+    };
+    let start = camera_position(&mut app);
+    for _ in 0..3 * 60 {
+        app.update();
+        assert!(camera_position(&mut app).x >= ground[0]);
+    }
+    assert!(camera_position(&mut app).x > start.x);
+}
@@ -0,0 +1,36 @@
+//! Control the unicycle from a training script over JSON lines
+//!
+//! Requests are read from stdin and answered on stdout, or over TCP with `--tcp <address>`.
+//! `--assets <folder>` points to the game's assets if not started from the repository.
+
+use game_plugin::{serve, Gym};
+use std::io::BufReader;
+use std::net::TcpListener;
+
+fn main() -> anyhow::Result<()> {
+    let assets = argument("--assets").unwrap_or_else(|| "assets".to_string());
+    // relative asset folders would be resolved from the executable's directory
+    let assets = std::fs::canonicalize(assets)?;
+    let mut gym = Gym::new(&assets.to_string_lossy())?;
+    match argument("--tcp") {
+        Some(address) => {
+            let listener = TcpListener::bind(&address)?;
+            // one training script at a time
+            for stream in listener.incoming() {
+                let stream = stream?;
+                serve(&mut gym, BufReader::new(stream.try_clone()?), stream)?;
+            }
+        }
+        None => {
+            let stdin = std::io::stdin();
+            serve(&mut gym, stdin.lock(), std::io::stdout())?;
+        }
+    }
+    Ok(())
+}
+
+fn argument(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
@@ -4,7 +4,7 @@
 #[cfg(target_arch = "wasm32")]
 use bevy_webgl2;
 
-use bevy::prelude::{App, ClearColor, Color, WindowDescriptor};
+use bevy::prelude::{App, WindowDescriptor};
 use bevy::DefaultPlugins;
 use game_plugin::GamePlugin;
 
@@ -16,7 +16,6 @@ fn main() {
             level: bevy::log::Level::WARN,
             filter: "wgpu=error,bevy_ecs=error".to_string(),
         })
-        .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
         .insert_resource(WindowDescriptor {
             width: 800.,
             height: 600.,